@@ -0,0 +1,64 @@
+#!/usr/bin/env python3
+# Downloads the official challenge's sample measurements and expected outputs into tests/samples/official
+#
+#     python3 scripts/official_samples.py [REF]
+#
+# REF is the branch, tag or commit of https://github.com/gunnarmorling/1brc to take them from, main by default.
+# The samples are under the Apache License 2.0, so a NOTICE saying where they came from is written next to them.
+# tests/samples.rs checks every pair it finds there along with the hand-written ones.
+
+import json
+import pathlib
+import sys
+import urllib.request
+
+REPO = "gunnarmorling/1brc"
+SAMPLES = "src/test/resources/samples"
+
+NOTICE = """\
+The files in this directory are the sample measurements and expected outputs of the
+One Billion Row Challenge, https://github.com/{repo}, taken unmodified from
+{samples} at {ref}.
+
+Copyright 2023 The original authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use these files except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+"""
+
+
+def get(url):
+    with urllib.request.urlopen(url) as response:
+        return response.read()
+
+
+def main():
+    ref = sys.argv[1] if len(sys.argv) > 1 else "main"
+    out = pathlib.Path(__file__).resolve().parent.parent / "tests" / "samples" / "official"
+    out.mkdir(parents=True, exist_ok=True)
+
+    listing = json.loads(get(f"https://api.github.com/repos/{REPO}/contents/{SAMPLES}?ref={ref}"))
+    names = {entry["name"] for entry in listing if entry["type"] == "file"}
+    # Only whole pairs, a measurements file without its output can't be checked
+    pairs = sorted(name for name in names if name.endswith(".txt") and name[:-4] + ".out" in names)
+    if not pairs:
+        sys.exit(f"no samples found in {REPO}/{SAMPLES} at {ref}")
+
+    for txt in pairs:
+        for name in (txt, txt[:-4] + ".out"):
+            (out / name).write_bytes(get(f"https://raw.githubusercontent.com/{REPO}/{ref}/{SAMPLES}/{name}"))
+    (out / "NOTICE").write_text(NOTICE.format(repo=REPO, samples=SAMPLES, ref=ref))
+    print(f"Wrote {len(pairs)} samples to {out}", file=sys.stderr)
+
+
+if __name__ == "__main__":
+    main()
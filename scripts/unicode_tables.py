@@ -0,0 +1,95 @@
+#!/usr/bin/env python3
+# Generates src/names/tables.rs from the Unicode database that comes with Python
+#
+#     python3 scripts/unicode_tables.py > src/names/tables.rs
+#
+# The tables follow whichever Unicode version this Python was built with, rerun it with a newer Python to update.
+
+import sys
+import unicodedata
+
+HANGUL = range(0xAC00, 0xD7A4)
+
+
+def chars():
+    for cp in range(0x110000):
+        # Surrogates can't be in a `char`
+        if 0xD800 <= cp <= 0xDFFF:
+            continue
+        yield chr(cp)
+
+
+def literal(s):
+    return '"' + "".join(c if 0x20 <= ord(c) < 0x7F and c not in '"\\' else f"\\u{{{ord(c):x}}}" for c in s) + '"'
+
+
+def char_literal(c):
+    return f"'\\u{{{ord(c):x}}}'"
+
+
+def main():
+    out = sys.stdout
+    out.write(
+        "// Generated by scripts/unicode_tables.py from Unicode "
+        f"{unicodedata.unidata_version}, don't edit by hand\n\n"
+    )
+
+    # Full canonical decompositions, Hangul syllables are decomposed arithmetically instead
+    decompositions = []
+    for c in chars():
+        if ord(c) in HANGUL:
+            continue
+        d = unicodedata.decomposition(c)
+        if d and not d.startswith("<"):
+            decompositions.append((c, unicodedata.normalize("NFD", c)))
+    out.write("/// Canonical decomposition of every character that has one, fully decomposed, by character\n")
+    out.write(f"pub(super) static DECOMPOSITIONS: [(char, &str); {len(decompositions)}] = [\n")
+    for c, d in decompositions:
+        out.write(f"    ({char_literal(c)}, {literal(d)}),\n")
+    out.write("];\n\n")
+
+    # Runs of characters with the same non-zero combining class
+    ranges = []
+    for c in chars():
+        ccc = unicodedata.combining(c)
+        if ccc == 0:
+            continue
+        if ranges and ord(ranges[-1][1]) + 1 == ord(c) and ranges[-1][2] == ccc:
+            ranges[-1][1] = c
+        else:
+            ranges.append([c, c, ccc])
+    out.write("/// Ranges of characters with a non-zero canonical combining class, by first character\n")
+    out.write(f"pub(super) static COMBINING_CLASSES: [(char, char, u8); {len(ranges)}] = [\n")
+    for first, last, ccc in ranges:
+        out.write(f"    ({char_literal(first)}, {char_literal(last)}, {ccc}),\n")
+    out.write("];\n\n")
+
+    # Pairs that compose, leaving out composition exclusions: a character is only composed to if composing its
+    # own decomposition gives it back
+    compositions = []
+    for c in chars():
+        if ord(c) in HANGUL:
+            continue
+        d = unicodedata.decomposition(c)
+        if not d or d.startswith("<"):
+            continue
+        parts = [chr(int(p, 16)) for p in d.split()]
+        if len(parts) == 2 and unicodedata.normalize("NFC", "".join(parts)) == c:
+            compositions.append((parts[0], parts[1], c))
+    compositions.sort()
+    out.write("/// Canonical compositions of pairs of characters, by pair\n")
+    out.write(f"pub(super) static COMPOSITIONS: [(char, char, char); {len(compositions)}] = [\n")
+    for first, second, c in compositions:
+        out.write(f"    ({char_literal(first)}, {char_literal(second)}, {char_literal(c)}),\n")
+    out.write("];\n\n")
+
+    # Full case folding (statuses C and F)
+    folds = [(c, c.casefold()) for c in chars() if c.casefold() != c]
+    out.write("/// Full case folding of every character that changes, by character\n")
+    out.write(f"pub(super) static CASE_FOLDING: [(char, &str); {len(folds)}] = [\n")
+    for c, f in folds:
+        out.write(f"    ({char_literal(c)}, {literal(f)}),\n")
+    out.write("];\n")
+
+
+main()
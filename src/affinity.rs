@@ -0,0 +1,191 @@
+// Pinning worker threads to CPUs, and finding out which CPUs share a NUMA node
+// Like the mapping, pinning goes straight to libc; the NUMA layout comes from sysfs
+
+use std::{fs, io, path::Path};
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod sys {
+    use std::ffi::{c_int, c_ulong};
+
+    /// Words in a CPU mask, enough for the 1024 CPUs glibc's `cpu_set_t` has room for
+    pub const MASK_WORDS: usize = 1024 / c_ulong::BITS as usize;
+
+    extern "C" {
+        pub fn sched_setaffinity(pid: c_int, cpusetsize: usize, mask: *const c_ulong) -> c_int;
+        pub fn sched_getaffinity(pid: c_int, cpusetsize: usize, mask: *mut c_ulong) -> c_int;
+    }
+}
+
+/// Where a worker thread was pinned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub cpu: usize,
+    /// Id of the NUMA node `cpu` belongs to
+    pub node: usize,
+}
+
+/// A NUMA node and the CPUs on it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub id: usize,
+    pub cpus: Vec<usize>,
+}
+
+/// The CPUs this process may run on, grouped by NUMA node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Topology {
+    /// Sorted by id, only nodes with at least one CPU we may run on
+    pub nodes: Vec<Node>,
+}
+
+impl Topology {
+    /// The CPUs this process is allowed on, and the nodes they're on
+    pub fn detect() -> io::Result<Self> {
+        Self::from_sysfs(Path::new("/sys/devices/system/node"), &allowed_cpus()?)
+    }
+
+    /// Group the CPUs in `allowed` by the nodes listed under `sysfs`, all in one node if there are none
+    pub fn from_sysfs(sysfs: &Path, allowed: &[usize]) -> io::Result<Self> {
+        let mut nodes = Vec::new();
+        // Kernels without NUMA support don't have the directory at all
+        if let Ok(dir) = fs::read_dir(sysfs) {
+            for entry in dir {
+                let entry = entry?;
+                let Some(id) = entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| name.strip_prefix("node"))
+                    .and_then(|id| id.parse().ok())
+                else {
+                    continue;
+                };
+                let list = fs::read_to_string(entry.path().join("cpulist"))?;
+                let cpus = parse_cpu_list(&list).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("bad CPU list for node {id}: {:?}", list.trim()),
+                    )
+                })?;
+                let cpus = cpus
+                    .into_iter()
+                    .filter(|cpu| allowed.contains(cpu))
+                    .collect::<Vec<_>>();
+                if !cpus.is_empty() {
+                    nodes.push(Node { id, cpus });
+                }
+            }
+        }
+        nodes.sort_unstable_by_key(|node| node.id);
+
+        if nodes.is_empty() {
+            nodes.push(Node {
+                id: 0,
+                cpus: allowed.to_vec(),
+            });
+        }
+        Ok(Self { nodes })
+    }
+
+    /// A CPU for each of `threads` threads, going round the nodes so they're spread as evenly as the CPUs allow
+    ///
+    /// Every CPU gets a thread before any gets a second one.
+    pub fn place(&self, threads: usize) -> Vec<Placement> {
+        let mut order = Vec::new();
+        let most = self.nodes.iter().map(|n| n.cpus.len()).max().unwrap_or(0);
+        for i in 0..most {
+            for node in &self.nodes {
+                if let Some(&cpu) = node.cpus.get(i) {
+                    order.push(Placement { cpu, node: node.id });
+                }
+            }
+        }
+        if order.is_empty() {
+            return Vec::new();
+        }
+        (0..threads).map(|t| order[t % order.len()]).collect()
+    }
+}
+
+/// Parse a kernel CPU list like `0-3,8-11,16`
+pub fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let list = list.trim();
+    if list.is_empty() {
+        return Some(Vec::new());
+    }
+    let mut cpus = Vec::new();
+    for range in list.split(',') {
+        match range.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (first.parse().ok()?, last.parse::<usize>().ok()?);
+                if first > last {
+                    return None;
+                }
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(range.parse().ok()?),
+        }
+    }
+    Some(cpus)
+}
+
+/// CPUs the calling thread may run on
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+fn allowed_cpus() -> io::Result<Vec<usize>> {
+    let mut mask = [0; sys::MASK_WORDS];
+    if unsafe { sys::sched_getaffinity(0, std::mem::size_of_val(&mask), mask.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let bits = u64::BITS as usize;
+    Ok((0..sys::MASK_WORDS * bits)
+        .filter(|&cpu| mask[cpu / bits] >> (cpu % bits) & 1 == 1)
+        .collect())
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+fn allowed_cpus() -> io::Result<Vec<usize>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "pinning threads isn't supported on this platform",
+    ))
+}
+
+/// Keep the calling thread on `cpu` from now on
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub fn pin_current_thread(cpu: usize) -> io::Result<()> {
+    let bits = u64::BITS as usize;
+    let mut mask = [0; sys::MASK_WORDS];
+    *mask.get_mut(cpu / bits).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("CPU {cpu} is out of range"),
+        )
+    })? |= 1 << (cpu % bits);
+    // A pid of 0 is the calling thread, not the whole process
+    if unsafe { sys::sched_setaffinity(0, std::mem::size_of_val(&mask), mask.as_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+pub fn pin_current_thread(_cpu: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "pinning threads isn't supported on this platform",
+    ))
+}
@@ -0,0 +1,349 @@
+// Benchmarking the whole pipeline, from mapping the file to printing the results
+// Every input is run several times so each phase gets a min/median/p95 instead of one noisy number,
+// dataset profiles are generated into temporary files first so they go through the same path as real files
+
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use onebrc_rs::{aggregate_file, generate, mmap, Options, Scanner, Table, ThreadTimings};
+
+use crate::{
+    cli::BenchArgs,
+    output::{json_string, write_results, Format, Metadata},
+};
+
+const PHASES: [&str; 7] = ["map", "chunk", "parse", "merge", "sort", "print", "total"];
+
+/// Timings of a single run
+struct Run {
+    /// In the same order as [`PHASES`]
+    phases: [Duration; PHASES.len()],
+    threads: Vec<ThreadTimings>,
+}
+
+/// All the timed runs of one input
+struct Summary {
+    name: String,
+    table: Table,
+    /// The one that actually ran, never [`Scanner::Auto`]
+    scanner: Scanner,
+    path: PathBuf,
+    bytes: u64,
+    rows: u64,
+    stations: usize,
+    runs: Vec<Run>,
+}
+
+/// A generated input, deleted when the benchmark is done with it
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Run the pipeline once, returning the timings, number of rows and number of stations
+fn run_once(path: &PathBuf, opts: &Options, evict: bool) -> onebrc_rs::Result<(Run, u64, usize)> {
+    if evict {
+        mmap::evict_from_page_cache(&File::open(path)?)?;
+    }
+
+    let instant = Instant::now();
+    let stats = aggregate_file(path, opts)?;
+
+    let sort_instant = Instant::now();
+    let entries = stats.sorted();
+    let sort = sort_instant.elapsed();
+
+    let print_instant = Instant::now();
+    let mut out = Vec::new();
+    let metadata = Metadata {
+        input: Some(path),
+        rejected: stats.rejects.total(),
+        timings: &stats.timings,
+        sort,
+        total: instant.elapsed(),
+    };
+    write_results(
+        &mut out,
+        &entries,
+        stats.decimals,
+        &Format::default(),
+        &metadata,
+    )?;
+    let print = print_instant.elapsed();
+
+    let total = instant.elapsed();
+    let t = &stats.timings;
+    let run = Run {
+        phases: [t.map, t.chunk, t.parse, t.merge, sort, print, total],
+        threads: t.threads.clone(),
+    };
+    let rows = t.threads.iter().map(|t| t.rows).sum();
+    Ok((run, rows, entries.len()))
+}
+
+fn bench_input(
+    name: String,
+    path: PathBuf,
+    args: &BenchArgs,
+    opts: &Options,
+) -> onebrc_rs::Result<Summary> {
+    if !args.quiet {
+        eprintln!(
+            "Benchmarking {name} using the {} table and {} scanner with {} warmup and {} timed runs",
+            opts.table.name(),
+            opts.scanner.resolve().name(),
+            args.warmup,
+            args.runs
+        );
+    }
+
+    for _ in 0..args.warmup {
+        run_once(&path, opts, args.evict)?;
+    }
+
+    let mut runs = Vec::with_capacity(args.runs);
+    let (mut rows, mut stations) = (0, 0);
+    for _ in 0..args.runs {
+        let (run, r, s) = run_once(&path, opts, args.evict)?;
+        runs.push(run);
+        (rows, stations) = (r, s);
+    }
+
+    Ok(Summary {
+        bytes: std::fs::metadata(&path)?.len(),
+        name,
+        table: opts.table,
+        scanner: opts.scanner.resolve(),
+        path,
+        rows,
+        stations,
+        runs,
+    })
+}
+
+/// Nearest-rank percentile of an already sorted, non-empty slice
+fn percentile<T: Copy>(sorted: &[T], p: f64) -> T {
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Min, median and p95 of one phase across all runs
+fn phase_stats(summary: &Summary, phase: usize) -> [Duration; 3] {
+    let mut times = summary
+        .runs
+        .iter()
+        .map(|r| r.phases[phase])
+        .collect::<Vec<_>>();
+    times.sort_unstable();
+    [times[0], percentile(&times, 0.5), percentile(&times, 0.95)]
+}
+
+/// Median rows/s and bytes/s of every thread across all runs
+fn thread_throughput(summary: &Summary) -> Vec<(f64, f64)> {
+    let threads = summary
+        .runs
+        .iter()
+        .map(|r| r.threads.len())
+        .min()
+        .unwrap_or(0);
+    (0..threads)
+        .map(|t| {
+            let median = |f: &dyn Fn(&ThreadTimings) -> u64| {
+                let mut rates = summary
+                    .runs
+                    .iter()
+                    .map(|r| f(&r.threads[t]) as f64 / r.threads[t].elapsed.as_secs_f64())
+                    .collect::<Vec<_>>();
+                rates.sort_unstable_by(f64::total_cmp);
+                percentile(&rates, 0.5)
+            };
+            (median(&|t| t.rows), median(&|t| t.bytes))
+        })
+        .collect()
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+fn print_table(summary: &Summary) {
+    println!(
+        "{} ({} table, {} scanner): {}, {:.1} MiB, {} rows, {} stations, {} runs",
+        summary.name,
+        summary.table.name(),
+        summary.scanner.name(),
+        summary.path.display(),
+        summary.bytes as f64 / (1024.0 * 1024.0),
+        summary.rows,
+        summary.stations,
+        summary.runs.len()
+    );
+    println!(
+        "  {:<8} {:>10} {:>10} {:>10}",
+        "phase", "min ms", "median ms", "p95 ms"
+    );
+    for (i, phase) in PHASES.iter().enumerate() {
+        let [min, median, p95] = phase_stats(summary, i);
+        println!(
+            "  {phase:<8} {:>10.2} {:>10.2} {:>10.2}",
+            ms(min),
+            ms(median),
+            ms(p95)
+        );
+    }
+    println!("  {:<8} {:>10} {:>10}", "thread", "Mrows/s", "GB/s");
+    for (t, (rows, bytes)) in thread_throughput(summary).into_iter().enumerate() {
+        println!("  {:<8} {:>10.2} {:>10.3}", t + 1, rows / 1e6, bytes / 1e9);
+    }
+    println!();
+}
+
+fn print_json(summaries: &[Summary], args: &BenchArgs) {
+    let inputs = summaries
+        .iter()
+        .map(|s| {
+            let phases = PHASES
+                .iter()
+                .enumerate()
+                .map(|(i, phase)| {
+                    let [min, median, p95] = phase_stats(s, i);
+                    format!(
+                        "\"{phase}\":{{\"min_ms\":{:.3},\"median_ms\":{:.3},\"p95_ms\":{:.3}}}",
+                        ms(min),
+                        ms(median),
+                        ms(p95)
+                    )
+                })
+                .collect::<Vec<_>>();
+            let threads = thread_throughput(s)
+                .into_iter()
+                .map(|(rows, bytes)| {
+                    format!(
+                        "{{\"rows_per_sec\":{rows:.0},\"gb_per_sec\":{:.4}}}",
+                        bytes / 1e9
+                    )
+                })
+                .collect::<Vec<_>>();
+            format!(
+                "{{\"name\":{},\"table\":\"{}\",\"scanner\":\"{}\",\"path\":{},\"bytes\":{},\"rows\":{},\"stations\":{},\"phases\":{{{}}},\"threads\":[{}]}}",
+                json_string(&s.name),
+                s.table.name(),
+                s.scanner.name(),
+                json_string(&s.path.to_string_lossy()),
+                s.bytes,
+                s.rows,
+                s.stations,
+                phases.join(","),
+                threads.join(",")
+            )
+        })
+        .collect::<Vec<_>>();
+
+    println!(
+        "{{\"runs\":{},\"warmup\":{},\"evict\":{},\"pin\":{},\"inputs\":[{}]}}",
+        args.runs,
+        args.warmup,
+        args.evict,
+        args.pin,
+        inputs.join(",")
+    );
+}
+
+/// Write `profile` into a temporary file to benchmark
+fn generate_profile(profile: generate::Profile, args: &BenchArgs) -> std::io::Result<TempFile> {
+    let path = std::env::temp_dir().join(format!(
+        "onebrc-bench-{}-{}.txt",
+        profile.name(),
+        std::process::id()
+    ));
+    let file = TempFile(path);
+
+    let config = generate::Config {
+        rows: args.rows,
+        seed: args.seed,
+        threads: args.threads,
+        ..profile.config()
+    };
+    generate::generate(BufWriter::new(File::create(&file.0)?), &config)?;
+    Ok(file)
+}
+
+/// Benchmark `path` once for every table and scanner asked for
+fn bench_tables(
+    name: &str,
+    path: &Path,
+    args: &BenchArgs,
+    opts: &Options,
+    summaries: &mut Vec<Summary>,
+) -> onebrc_rs::Result<()> {
+    for &table in &args.tables {
+        for &scanner in &args.scanners {
+            let opts = Options {
+                table,
+                scanner,
+                ..opts.clone()
+            };
+            summaries.push(bench_input(
+                name.to_string(),
+                path.to_path_buf(),
+                args,
+                &opts,
+            )?);
+            if !args.json {
+                print_table(summaries.last().unwrap());
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn run(args: BenchArgs) -> ExitCode {
+    let opts = Options {
+        threads: args.threads,
+        chunk_size: args.chunk_size,
+        pin: args.pin,
+        ..Options::default()
+    };
+
+    let mut summaries = Vec::new();
+
+    for path in &args.inputs {
+        let name = path.display().to_string();
+        if let Err(e) = bench_tables(&name, path, &args, &opts, &mut summaries) {
+            eprintln!("error: {name}: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    for &profile in &args.profiles {
+        if !args.quiet {
+            eprintln!("Generating {} rows for {}", args.rows, profile.name());
+        }
+        let file = match generate_profile(profile, &args) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("error: generating {}: {e}", profile.name());
+                return ExitCode::FAILURE;
+            }
+        };
+
+        if let Err(e) = bench_tables(profile.name(), &file.0, &args, &opts, &mut summaries) {
+            eprintln!("error: {}: {e}", profile.name());
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if args.json {
+        print_json(&summaries, &args);
+    }
+
+    ExitCode::SUCCESS
+}
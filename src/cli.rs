@@ -0,0 +1,764 @@
+// Hand-rolled argument parsing, we don't want to pull in clap just for a handful of flags
+
+use std::{fmt, num::NonZeroUsize, path::PathBuf};
+
+use onebrc_rs::{
+    generate::{NameLengths, Profile, MAX_STATIONS},
+    Collation, Encoding, ErrorPolicy, ExcessPrecision, Scanner, Table, DEFAULT_CHUNK_SIZE,
+    DEFAULT_MAX_REJECTS, MAX_DECIMALS,
+};
+
+pub const USAGE: &str = "\
+Usage: onebrc-rs [OPTIONS] [FILE]
+       onebrc-rs generate [OPTIONS] [ROWS]
+       onebrc-rs bench [OPTIONS] [PROFILE]...
+
+Calculates the min, mean and max temperature of every weather station in FILE.
+
+Commands:
+  generate  Write a measurements file to process, see `onebrc-rs generate --help`
+  bench     Time processing each dataset profile, see `onebrc-rs bench --help`
+
+Arguments:
+  [FILE]  Measurements file to read, - for stdin [default: measurements.txt]
+
+Options:
+  -t, --threads <N>        Number of worker threads [default: available cores]
+  -s, --separator <CHAR>   Separator between station name and temperature [default: ;]
+      --encoding <ENCODING>
+                           Encoding of the input: utf8, latin1, or bytes to take names as they are and
+                           escape invalid UTF-8 as \\xFC when printing them, or lossy to replace it with
+                           U+FFFD instead [default: utf8]
+      --trim               Ignore whitespace around station names
+      --fold-case          Ignore case in station names
+      --nfc                Treat composed and decomposed accents in station names the same
+      --normalize          All of --trim, --fold-case and --nfc
+      --alias <FROM=TO>    Count station FROM as TO, after normalizing it, can be given more than once
+      --alias-file <PATH>  Read aliases from a file, one FROM=TO per line, # starts a comment
+      --collation <ORDER>  Order of the output: codepoint, or unicode to sort like a dictionary [default: codepoint]
+  -d, --decimals <N>       Digits after the decimal point, from 1 to 4 [default: 1]
+      --excess-precision <POLICY>
+                           What to do with values with more digits: round, reject [default: round]
+  -f, --format <FORMAT>    Output format: brace, lines, json, csv, tsv [default: brace]
+      --columns <LIST>     Columns for csv and tsv: name, min, mean, max, count, sum, and stddev when built with
+                           the stddev feature [default: name,min,mean,max]
+      --decimal-separator <CHAR>
+                           Decimal point for csv and tsv, like , for European spreadsheets [default: .]
+      --stream             Read the file in blocks instead of mapping it into memory
+      --chunk-size <BYTES> Bytes of the file threads take at a time, with an optional K, M or G suffix [default: 4M]
+      --on-error <POLICY>  What to do with bad lines: fail, skip, report [default: fail]
+      --reject-file <PATH> Where to write bad lines with --on-error=report [default: FILE.rejects]
+      --max-rejects <N>    Most bad lines to write with --on-error=report, the rest are only counted [default: 10000]
+      --checked            Fail if a station's sum overflows instead of wrapping around
+      --table <TABLE>      Hash table for the stations: open, std [default: open]
+      --scanner <SCANNER>  How lines are scanned: auto, avx2, sse2, neon, swar [default: auto]
+      --pin                Pin each thread to a CPU, spread over NUMA nodes, and report where they went
+  -q, --quiet              Don't print logs and timings to stderr
+  -h, --help               Print this help
+";
+
+pub const GENERATE_USAGE: &str = "\
+Usage: onebrc-rs generate [OPTIONS] [ROWS]
+
+Writes ROWS random measurements, from the official list of weather stations and their mean temperatures.
+
+Arguments:
+  [ROWS]  Number of rows to write [default: 1000000000]
+
+Options:
+  -o, --output <PATH>      File to write, - for stdout [default: measurements.txt]
+  -p, --profile <NAME>     Start from a dataset profile, the options below override it [default: classic]
+                           classic: the official 413 stations
+                           10k: 10,000 unique stations
+                           long-names: names of 90 to 100 bytes of UTF-8
+                           collisions: 10,000 names with colliding hashes
+                           zipf: a few stations get most of the rows
+      --stations <N>       Number of distinct stations, made-up ones are added past the official 413 [default: 413]
+      --seed <N>           Seed for the random numbers, the same seed gives the same file [default: random]
+  -t, --threads <N>        Number of generating threads [default: available cores]
+      --name-length <DIST> Station name lengths: official, short, long, uniform [default: official]
+      --zipf <S>           Pick stations with Zipfian frequencies with exponent S instead of uniformly
+      --adversarial        Mix in extreme values, negative zeros and awkward station names
+  -q, --quiet              Don't print progress to stderr
+  -h, --help               Print this help
+";
+
+const DEFAULT_FILE_NAME: &str = "measurements.txt";
+const DEFAULT_SEPARATOR: char = ';';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The format from the challenge, `{name=min/mean/max, ...}`
+    Brace,
+    /// One `name=min/mean/max` per line
+    Lines,
+    /// Every station's min, max, mean, sum and count, and how the run went
+    Json,
+    /// Comma-separated values with a header row, quoted as in RFC 4180
+    Csv,
+    /// Like [`Csv`](Self::Csv), with tabs
+    Tsv,
+}
+
+impl OutputFormat {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "brace" => Some(Self::Brace),
+            "lines" => Some(Self::Lines),
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            "tsv" => Some(Self::Tsv),
+            _ => None,
+        }
+    }
+}
+
+/// A column of the CSV and TSV formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Name,
+    Min,
+    Mean,
+    Max,
+    Count,
+    Sum,
+    /// Population standard deviation, needs the `stddev` feature
+    Stddev,
+}
+
+impl Column {
+    pub const ALL: [Self; 7] = [
+        Self::Name,
+        Self::Min,
+        Self::Mean,
+        Self::Max,
+        Self::Count,
+        Self::Sum,
+        Self::Stddev,
+    ];
+
+    /// The columns of the challenge's own format
+    pub const DEFAULT: [Self; 4] = [Self::Name, Self::Min, Self::Mean, Self::Max];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Min => "min",
+            Self::Mean => "mean",
+            Self::Max => "max",
+            Self::Count => "count",
+            Self::Sum => "sum",
+            Self::Stddev => "stddev",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    /// Whether this build keeps what the column needs
+    pub fn is_available(self) -> bool {
+        self != Self::Stddev || cfg!(feature = "stddev")
+    }
+}
+
+#[derive(Debug)]
+pub struct Args {
+    pub path: PathBuf,
+    pub threads: Option<NonZeroUsize>,
+    pub separator: char,
+    pub encoding: Encoding,
+    pub trim: bool,
+    pub fold_case: bool,
+    pub nfc: bool,
+    pub aliases: Vec<(String, String)>,
+    pub alias_files: Vec<PathBuf>,
+    pub collation: Collation,
+    pub decimals: u8,
+    pub excess_precision: ExcessPrecision,
+    pub format: OutputFormat,
+    pub columns: Vec<Column>,
+    pub decimal_separator: char,
+    pub stream: bool,
+    pub chunk_size: usize,
+    pub on_error: ErrorPolicy,
+    pub reject_file: Option<PathBuf>,
+    pub max_rejects: usize,
+    pub checked: bool,
+    pub table: Table,
+    pub scanner: Scanner,
+    pub pin: bool,
+    pub quiet: bool,
+}
+
+#[derive(Debug)]
+pub struct GenerateArgs {
+    pub rows: u64,
+    pub output: PathBuf,
+    pub profile: Profile,
+    // Overrides for the profile
+    pub stations: Option<usize>,
+    pub name_lengths: Option<NameLengths>,
+    pub zipf: Option<f64>,
+    pub adversarial: bool,
+    pub seed: Option<u64>,
+    pub threads: Option<NonZeroUsize>,
+    pub quiet: bool,
+}
+
+pub const BENCH_USAGE: &str = "\
+Usage: onebrc-rs bench [OPTIONS] [PROFILE]...
+
+Runs the whole pipeline several times over each dataset profile and reports how long every phase took,
+so slowdowns on one shape of input stand out. Profiles are generated into temporary files first.
+
+Arguments:
+  [PROFILE]...  Profiles to run: classic, 10k, long-names, collisions, zipf [default: all of them, unless --input is given]
+
+Options:
+  -i, --input <FILE>       Also benchmark an existing measurements file, can be given more than once
+  -r, --runs <N>           Timed runs per input [default: 5]
+  -w, --warmup <N>         Untimed runs before those [default: 1]
+      --evict              Drop the input from the page cache before every run, to include reading it from disk
+      --json               Print the results as JSON instead of a table
+  -n, --rows <N>           Rows per profile [default: 5000000]
+      --seed <N>           Seed for the generated data [default: 0]
+  -t, --threads <N>        Number of worker threads [default: available cores]
+      --chunk-size <BYTES> Bytes of the file threads take at a time, with an optional K, M or G suffix [default: 4M]
+      --table <TABLE>      Hash table for the stations: open, std, can be given more than once to compare [default: open]
+      --scanner <SCANNER>  How lines are scanned: auto, avx2, sse2, neon, swar, can be given more than once [default: auto]
+      --pin                Pin each thread to a CPU, spread over NUMA nodes
+  -q, --quiet              Don't print progress to stderr
+  -h, --help               Print this help
+";
+
+#[derive(Debug)]
+pub struct BenchArgs {
+    pub profiles: Vec<Profile>,
+    pub inputs: Vec<PathBuf>,
+    pub runs: usize,
+    pub warmup: usize,
+    pub evict: bool,
+    pub json: bool,
+    pub rows: u64,
+    pub seed: u64,
+    pub threads: Option<NonZeroUsize>,
+    pub chunk_size: usize,
+    pub tables: Vec<Table>,
+    pub scanners: Vec<Scanner>,
+    pub pin: bool,
+    pub quiet: bool,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Run(Args),
+    Generate(GenerateArgs),
+    Bench(BenchArgs),
+    Help,
+}
+
+#[derive(Debug)]
+pub enum CliError {
+    UnknownOption(String),
+    MissingValue(&'static str),
+    InvalidValue(&'static str, String),
+    UnexpectedArgument(String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownOption(opt) => write!(f, "unknown option '{opt}'"),
+            Self::MissingValue(opt) => write!(f, "option '{opt}' requires a value"),
+            Self::InvalidValue(opt, value) => {
+                write!(f, "invalid value '{value}' for option '{opt}'")
+            }
+            Self::UnexpectedArgument(arg) => write!(f, "unexpected argument '{arg}'"),
+        }
+    }
+}
+
+fn parse_error_policy(value: &str) -> Option<ErrorPolicy> {
+    match value {
+        "fail" => Some(ErrorPolicy::Fail),
+        "skip" => Some(ErrorPolicy::Skip),
+        "report" => Some(ErrorPolicy::Report),
+        _ => None,
+    }
+}
+
+/// A `FROM=TO` alias, from an argument or a line of an alias file
+pub fn parse_alias(value: &str) -> Option<(String, String)> {
+    let (from, to) = value.split_once('=')?;
+    Some((from.to_string(), to.to_string()))
+}
+
+/// A comma-separated list of columns, `stddev` only if it's being kept track of
+fn parse_columns(value: &str) -> Option<Vec<Column>> {
+    value
+        .split(',')
+        .map(|name| Column::from_name(name).filter(|c| c.is_available()))
+        .collect()
+}
+
+/// Anything that can't be confused with the digits or the sign, and doesn't need quoting in every field
+fn parse_decimal_separator(value: &str) -> Option<char> {
+    let mut chars = value.chars();
+    let c = chars.next()?;
+    // Control characters include CR and LF
+    if chars.next().is_some() || c.is_ascii_digit() || c == '-' || c == '"' || c.is_control() {
+        return None;
+    }
+    Some(c)
+}
+
+/// Only the scanners this CPU can run
+fn parse_scanner(value: &str) -> Option<Scanner> {
+    Scanner::from_name(value).filter(|s| s.is_supported())
+}
+
+/// A non-zero number of bytes, optionally in KiB, MiB or GiB
+fn parse_size(value: &str) -> Option<usize> {
+    let (digits, unit) = match value.char_indices().last()? {
+        (i, 'K' | 'k') => (&value[..i], 1 << 10),
+        (i, 'M' | 'm') => (&value[..i], 1 << 20),
+        (i, 'G' | 'g') => (&value[..i], 1 << 30),
+        _ => (value, 1),
+    };
+    parse_count::<usize>(digits)?
+        .checked_mul(unit)
+        .filter(|&n| n > 0)
+}
+
+fn parse_name_lengths(value: &str) -> Option<NameLengths> {
+    match value {
+        "official" => Some(NameLengths::Official),
+        "short" => Some(NameLengths::Short),
+        "long" => Some(NameLengths::Long),
+        "uniform" => Some(NameLengths::Uniform),
+        _ => None,
+    }
+}
+
+/// Parse a count, allowing `_` between digits like `1_000_000_000`
+fn parse_count<T: std::str::FromStr>(value: &str) -> Option<T> {
+    if value.starts_with('_') {
+        return None;
+    }
+    value.replace('_', "").parse().ok()
+}
+
+fn parse_separator(value: &str) -> Option<char> {
+    let c = match value {
+        "\\t" | "tab" => '\t',
+        _ => {
+            let mut chars = value.chars();
+            let c = chars.next()?;
+            if chars.next().is_some() {
+                return None;
+            }
+            c
+        }
+    };
+    // A newline would make every line look like it's missing a temperature
+    (c != '\n' && c != '\r').then_some(c)
+}
+
+/// Parse the arguments, not including the program name
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, CliError> {
+    let mut args = args.into_iter();
+
+    let mut path = None;
+    let mut threads = None;
+    let mut separator = DEFAULT_SEPARATOR;
+    let mut encoding = Encoding::Utf8;
+    let (mut trim, mut fold_case, mut nfc) = (false, false, false);
+    let mut aliases = Vec::new();
+    let mut alias_files = Vec::new();
+    let mut collation = Collation::Codepoint;
+    let mut decimals = 1;
+    let mut excess_precision = ExcessPrecision::Round;
+    let mut format = OutputFormat::Brace;
+    let mut columns = Column::DEFAULT.to_vec();
+    let mut decimal_separator = '.';
+    let mut stream = false;
+    let mut chunk_size = DEFAULT_CHUNK_SIZE;
+    let mut on_error = ErrorPolicy::Fail;
+    let mut reject_file = None;
+    let mut max_rejects = DEFAULT_MAX_REJECTS;
+    let mut checked = false;
+    let mut table = Table::Open;
+    let mut scanner = Scanner::Auto;
+    let mut pin = false;
+    let mut quiet = false;
+    let mut only_positional = false;
+
+    while let Some(arg) = args.next() {
+        if only_positional || arg == "-" || !arg.starts_with('-') {
+            if path.is_some() {
+                return Err(CliError::UnexpectedArgument(arg));
+            }
+            path = Some(PathBuf::from(arg));
+            continue;
+        }
+
+        // Support both `--opt value` and `--opt=value`
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+
+        let mut value = |opt: &'static str| {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or(CliError::MissingValue(opt))
+        };
+
+        match name {
+            "--" => only_positional = true,
+            "-h" | "--help" => return Ok(Command::Help),
+            "-q" | "--quiet" => quiet = true,
+            "--stream" => stream = true,
+            "--pin" => pin = true,
+            "--chunk-size" => {
+                let v = value("--chunk-size")?;
+                chunk_size = parse_size(&v).ok_or(CliError::InvalidValue("--chunk-size", v))?;
+            }
+            "--checked" => checked = true,
+            "--trim" => trim = true,
+            "--fold-case" => fold_case = true,
+            "--nfc" => nfc = true,
+            "--normalize" => (trim, fold_case, nfc) = (true, true, true),
+            "--alias" => {
+                let v = value("--alias")?;
+                aliases.push(parse_alias(&v).ok_or(CliError::InvalidValue("--alias", v))?);
+            }
+            "--alias-file" => alias_files.push(PathBuf::from(value("--alias-file")?)),
+            "--collation" => {
+                let v = value("--collation")?;
+                collation =
+                    Collation::from_name(&v).ok_or(CliError::InvalidValue("--collation", v))?;
+            }
+            "--encoding" => {
+                let v = value("--encoding")?;
+                encoding =
+                    Encoding::from_name(&v).ok_or(CliError::InvalidValue("--encoding", v))?;
+            }
+            "--table" => {
+                let v = value("--table")?;
+                table = Table::from_name(&v).ok_or(CliError::InvalidValue("--table", v))?;
+            }
+            "--scanner" => {
+                let v = value("--scanner")?;
+                scanner = parse_scanner(&v).ok_or(CliError::InvalidValue("--scanner", v))?;
+            }
+            "--on-error" => {
+                let v = value("--on-error")?;
+                on_error = parse_error_policy(&v).ok_or(CliError::InvalidValue("--on-error", v))?;
+            }
+            "--reject-file" => reject_file = Some(PathBuf::from(value("--reject-file")?)),
+            "--max-rejects" => {
+                let v = value("--max-rejects")?;
+                max_rejects = v
+                    .parse()
+                    .map_err(|_| CliError::InvalidValue("--max-rejects", v))?;
+            }
+            "-t" | "--threads" => {
+                let v = value("--threads")?;
+                threads = Some(
+                    v.parse::<NonZeroUsize>()
+                        .map_err(|_| CliError::InvalidValue("--threads", v))?,
+                );
+            }
+            "-s" | "--separator" => {
+                let v = value("--separator")?;
+                separator = parse_separator(&v).ok_or(CliError::InvalidValue("--separator", v))?;
+            }
+            "-d" | "--decimals" => {
+                let v = value("--decimals")?;
+                decimals = match v.parse::<u8>() {
+                    Ok(d) if (1..=MAX_DECIMALS).contains(&d) => d,
+                    _ => return Err(CliError::InvalidValue("--decimals", v)),
+                };
+            }
+            "--excess-precision" => {
+                let v = value("--excess-precision")?;
+                excess_precision = match v.as_str() {
+                    "round" => ExcessPrecision::Round,
+                    "reject" => ExcessPrecision::Reject,
+                    _ => return Err(CliError::InvalidValue("--excess-precision", v)),
+                };
+            }
+            "-f" | "--format" => {
+                let v = value("--format")?;
+                format =
+                    OutputFormat::from_name(&v).ok_or(CliError::InvalidValue("--format", v))?;
+            }
+            "--columns" => {
+                let v = value("--columns")?;
+                columns = parse_columns(&v).ok_or(CliError::InvalidValue("--columns", v))?;
+            }
+            "--decimal-separator" => {
+                let v = value("--decimal-separator")?;
+                decimal_separator = parse_decimal_separator(&v)
+                    .ok_or(CliError::InvalidValue("--decimal-separator", v))?;
+            }
+            _ => return Err(CliError::UnknownOption(arg)),
+        }
+    }
+
+    // A Latin-1 file can't have anything past U+00FF in it
+    if encoding == Encoding::Latin1 && separator as u32 > 0xFF {
+        return Err(CliError::InvalidValue("--separator", separator.to_string()));
+    }
+
+    Ok(Command::Run(Args {
+        path: path.unwrap_or_else(|| PathBuf::from(DEFAULT_FILE_NAME)),
+        threads,
+        separator,
+        encoding,
+        trim,
+        fold_case,
+        nfc,
+        aliases,
+        alias_files,
+        collation,
+        decimals,
+        excess_precision,
+        format,
+        columns,
+        decimal_separator,
+        stream,
+        chunk_size,
+        on_error,
+        reject_file,
+        max_rejects,
+        checked,
+        table,
+        scanner,
+        pin,
+        quiet,
+    }))
+}
+
+/// Parse the arguments of the `generate` command, not including the command itself
+pub fn parse_generate(args: impl IntoIterator<Item = String>) -> Result<Command, CliError> {
+    let mut args = args.into_iter();
+
+    let mut rows = None;
+    let mut output = None;
+    let mut profile = Profile::Classic;
+    let mut stations = None;
+    let mut name_lengths = None;
+    let mut zipf = None;
+    let mut adversarial = false;
+    let mut seed = None;
+    let mut threads = None;
+    let mut quiet = false;
+    let mut only_positional = false;
+
+    while let Some(arg) = args.next() {
+        if only_positional || !arg.starts_with('-') {
+            if rows.is_some() {
+                return Err(CliError::UnexpectedArgument(arg));
+            }
+            rows = Some(parse_count(&arg).ok_or(CliError::InvalidValue("ROWS", arg))?);
+            continue;
+        }
+
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+
+        let mut value = |opt: &'static str| {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or(CliError::MissingValue(opt))
+        };
+
+        match name {
+            "--" => only_positional = true,
+            "-h" | "--help" => return Ok(Command::Help),
+            "-q" | "--quiet" => quiet = true,
+            "--adversarial" => adversarial = true,
+            "-o" | "--output" => output = Some(PathBuf::from(value("--output")?)),
+            "-p" | "--profile" => {
+                let v = value("--profile")?;
+                profile = Profile::from_name(&v).ok_or(CliError::InvalidValue("--profile", v))?;
+            }
+            "--stations" => {
+                let v = value("--stations")?;
+                stations = match parse_count(&v) {
+                    Some(n) if (1..=MAX_STATIONS).contains(&n) => Some(n),
+                    _ => return Err(CliError::InvalidValue("--stations", v)),
+                };
+            }
+            "--zipf" => {
+                let v = value("--zipf")?;
+                zipf = match v.parse::<f64>() {
+                    Ok(s) if s.is_finite() && s >= 0.0 => Some(s),
+                    _ => return Err(CliError::InvalidValue("--zipf", v)),
+                };
+            }
+            "--seed" => {
+                let v = value("--seed")?;
+                seed = Some(parse_count(&v).ok_or(CliError::InvalidValue("--seed", v))?);
+            }
+            "-t" | "--threads" => {
+                let v = value("--threads")?;
+                threads = Some(
+                    v.parse::<NonZeroUsize>()
+                        .map_err(|_| CliError::InvalidValue("--threads", v))?,
+                );
+            }
+            "--name-length" => {
+                let v = value("--name-length")?;
+                name_lengths =
+                    Some(parse_name_lengths(&v).ok_or(CliError::InvalidValue("--name-length", v))?);
+            }
+            _ => return Err(CliError::UnknownOption(arg)),
+        }
+    }
+
+    Ok(Command::Generate(GenerateArgs {
+        rows: rows.unwrap_or(1_000_000_000),
+        output: output.unwrap_or_else(|| PathBuf::from(DEFAULT_FILE_NAME)),
+        profile,
+        stations,
+        name_lengths,
+        zipf,
+        adversarial,
+        seed,
+        threads,
+        quiet,
+    }))
+}
+
+/// Parse the arguments of the `bench` command, not including the command itself
+pub fn parse_bench(args: impl IntoIterator<Item = String>) -> Result<Command, CliError> {
+    let mut args = args.into_iter();
+
+    let mut profiles = Vec::new();
+    let mut inputs = Vec::new();
+    let mut runs = 5;
+    let mut warmup = 1;
+    let mut evict = false;
+    let mut pin = false;
+    let mut json = false;
+    let mut rows = 5_000_000;
+    let mut seed = 0;
+    let mut threads = None;
+    let mut chunk_size = DEFAULT_CHUNK_SIZE;
+    let mut tables = Vec::new();
+    let mut scanners = Vec::new();
+    let mut quiet = false;
+    let mut only_positional = false;
+
+    while let Some(arg) = args.next() {
+        if only_positional || !arg.starts_with('-') {
+            profiles.push(Profile::from_name(&arg).ok_or(CliError::InvalidValue("PROFILE", arg))?);
+            continue;
+        }
+
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+
+        let mut value = |opt: &'static str| {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or(CliError::MissingValue(opt))
+        };
+
+        match name {
+            "--" => only_positional = true,
+            "-h" | "--help" => return Ok(Command::Help),
+            "-q" | "--quiet" => quiet = true,
+            "--evict" => evict = true,
+            "--pin" => pin = true,
+            "--json" => json = true,
+            "--chunk-size" => {
+                let v = value("--chunk-size")?;
+                chunk_size = parse_size(&v).ok_or(CliError::InvalidValue("--chunk-size", v))?;
+            }
+            "--table" => {
+                let v = value("--table")?;
+                let table = Table::from_name(&v).ok_or(CliError::InvalidValue("--table", v))?;
+                if !tables.contains(&table) {
+                    tables.push(table);
+                }
+            }
+            "--scanner" => {
+                let v = value("--scanner")?;
+                let scanner = parse_scanner(&v).ok_or(CliError::InvalidValue("--scanner", v))?;
+                if !scanners.contains(&scanner) {
+                    scanners.push(scanner);
+                }
+            }
+            "-i" | "--input" => inputs.push(PathBuf::from(value("--input")?)),
+            "-r" | "--runs" => {
+                let v = value("--runs")?;
+                runs = match parse_count(&v) {
+                    Some(n) if n > 0 => n,
+                    _ => return Err(CliError::InvalidValue("--runs", v)),
+                };
+            }
+            "-w" | "--warmup" => {
+                let v = value("--warmup")?;
+                warmup = parse_count(&v).ok_or(CliError::InvalidValue("--warmup", v))?;
+            }
+            "-n" | "--rows" => {
+                let v = value("--rows")?;
+                rows = match parse_count(&v) {
+                    Some(n) if n > 0 => n,
+                    _ => return Err(CliError::InvalidValue("--rows", v)),
+                };
+            }
+            "--seed" => {
+                let v = value("--seed")?;
+                seed = parse_count(&v).ok_or(CliError::InvalidValue("--seed", v))?;
+            }
+            "-t" | "--threads" => {
+                let v = value("--threads")?;
+                threads = Some(
+                    v.parse::<NonZeroUsize>()
+                        .map_err(|_| CliError::InvalidValue("--threads", v))?,
+                );
+            }
+            _ => return Err(CliError::UnknownOption(arg)),
+        }
+    }
+
+    if profiles.is_empty() && inputs.is_empty() {
+        profiles = Profile::ALL.to_vec();
+    }
+    if tables.is_empty() {
+        tables.push(Table::Open);
+    }
+    if scanners.is_empty() {
+        scanners.push(Scanner::Auto);
+    }
+
+    Ok(Command::Bench(BenchArgs {
+        profiles,
+        inputs,
+        runs,
+        warmup,
+        evict,
+        json,
+        rows,
+        seed,
+        threads,
+        chunk_size,
+        tables,
+        scanners,
+        pin,
+        quiet,
+    }))
+}
@@ -0,0 +1,225 @@
+use std::{fmt, io};
+
+use crate::Options;
+
+const SNIPPET_LEN: usize = 120; // Enough for a 100 byte name, the separator and a temperature
+
+/// Where in the input a bad line starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    /// Byte offset of the start of the line
+    pub offset: u64,
+    /// 1-based line number
+    pub line: u64,
+}
+
+/// A line that couldn't be parsed, with enough context to find it again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineError {
+    pub at: Location,
+    /// The whole offending line, lossily converted to UTF-8
+    pub content: String,
+}
+
+impl LineError {
+    pub(crate) fn new(offset: usize, line: usize, bytes: &[u8]) -> Self {
+        let len = bytes
+            .iter()
+            .position(|&b| b == b'\n')
+            .unwrap_or(bytes.len());
+        Self {
+            at: Location {
+                offset: offset as u64,
+                line: line as u64,
+            },
+            content: String::from_utf8_lossy(&bytes[..len]).into_owned(),
+        }
+    }
+
+    /// The start of the offending line, short enough to show in a message
+    pub fn snippet(&self) -> &str {
+        let mut end = self.content.len().min(SNIPPET_LEN);
+        while !self.content.is_char_boundary(end) {
+            end -= 1;
+        }
+        &self.content[..end]
+    }
+
+    /// Move a location relative to a chunk of the input to one relative to the whole input
+    fn relocate(&mut self, offset: usize, lines: usize) {
+        self.at.offset += offset as u64;
+        self.at.line += lines as u64;
+    }
+}
+
+/// Number of newlines in `bytes`, for turning offsets into line numbers
+pub(crate) fn count_lines(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|&&b| b == b'\n').count()
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Reading the input failed
+    Io(io::Error),
+    /// The input could be opened but not mapped into memory
+    Mmap(io::Error),
+    /// A line without the separator between the station name and temperature
+    MissingSeparator(LineError),
+    /// A temperature that isn't a number
+    BadTemperature(LineError),
+    /// A station name that isn't valid UTF-8
+    InvalidUtf8(LineError),
+    /// The sum of a station's measurements doesn't fit in an i64, built without the `i128-sum` feature
+    Overflow(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// The bad line this error is about, if any
+    pub fn line_error(&self) -> Option<&LineError> {
+        match self {
+            Self::Io(_) | Self::Mmap(_) | Self::Overflow(_) => None,
+            Self::MissingSeparator(e) | Self::BadTemperature(e) | Self::InvalidUtf8(e) => Some(e),
+        }
+    }
+
+    /// Short description of what's wrong
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::Io(_) => "I/O error",
+            Self::Mmap(_) => "mapping failed",
+            Self::Overflow(_) => "sum overflowed",
+            Self::MissingSeparator(_) => "missing separator",
+            Self::BadTemperature(_) => "invalid temperature",
+            Self::InvalidUtf8(_) => "invalid UTF-8",
+        }
+    }
+
+    fn line_error_mut(&mut self) -> Option<&mut LineError> {
+        match self {
+            Self::Io(_) | Self::Mmap(_) | Self::Overflow(_) => None,
+            Self::MissingSeparator(e) | Self::BadTemperature(e) | Self::InvalidUtf8(e) => Some(e),
+        }
+    }
+
+    /// See [`LineError::relocate`]
+    pub(crate) fn relocate(mut self, offset: usize, lines: usize) -> Self {
+        if let Some(e) = self.line_error_mut() {
+            e.relocate(offset, lines);
+        }
+        self
+    }
+}
+
+/// What to do with lines that can't be parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Stop at the first bad line
+    #[default]
+    Fail,
+    /// Skip bad lines, only counting them
+    Skip,
+    /// Skip bad lines, keeping them around so they can be reported
+    Report,
+}
+
+/// Lines that were skipped because they couldn't be parsed
+#[derive(Debug, Default)]
+pub struct Rejects {
+    pub missing_separator: u64,
+    pub bad_temperature: u64,
+    pub invalid_utf8: u64,
+    /// The first of the rejected lines, only kept with [`ErrorPolicy::Report`] and up to
+    /// [`Options::max_rejects`] of them
+    pub lines: Vec<Error>,
+}
+
+impl Rejects {
+    /// Handle a bad line according to [`Options::on_error`], giving the error back if we should stop
+    #[cold]
+    pub(crate) fn reject(&mut self, e: Error, opts: &Options) -> Result<()> {
+        match &e {
+            _ if opts.on_error == ErrorPolicy::Fail => return Err(e),
+            Error::MissingSeparator(_) => self.missing_separator += 1,
+            Error::BadTemperature(_) => self.bad_temperature += 1,
+            Error::InvalidUtf8(_) => self.invalid_utf8 += 1,
+            Error::Io(_) | Error::Mmap(_) | Error::Overflow(_) => return Err(e),
+        }
+        if opts.on_error == ErrorPolicy::Report && self.lines.len() < opts.max_rejects {
+            self.lines.push(e);
+        }
+        Ok(())
+    }
+
+    /// Drop the lines past the first `budget`, and take the ones left off it
+    pub(crate) fn keep_within(&mut self, budget: &mut usize) {
+        self.lines.truncate(*budget);
+        *budget -= self.lines.len();
+    }
+
+    pub fn total(&self) -> u64 {
+        self.missing_separator + self.bad_temperature + self.invalid_utf8
+    }
+
+    pub fn merge(&mut self, other: Self) {
+        self.missing_separator += other.missing_separator;
+        self.bad_temperature += other.bad_temperature;
+        self.invalid_utf8 += other.invalid_utf8;
+        self.lines.extend(other.lines);
+    }
+
+    /// See [`Error::relocate`]
+    pub(crate) fn relocate(&mut self, offset: usize, lines: usize) {
+        for e in &mut self.lines {
+            if let Some(e) = e.line_error_mut() {
+                e.relocate(offset, lines);
+            }
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (what, e) = match self {
+            Self::Io(e) => return write!(f, "{e}"),
+            Self::Mmap(e) => return write!(f, "couldn't map file into memory: {e}"),
+            Self::Overflow(name) => {
+                return write!(
+                    f,
+                    "sum of measurements for '{name}' overflowed, try building with the i128-sum feature"
+                )
+            }
+            Self::MissingSeparator(e) | Self::BadTemperature(e) | Self::InvalidUtf8(e) => {
+                (self.reason(), e)
+            }
+        };
+
+        let gutter = e.at.line.to_string();
+        write!(
+            f,
+            "{what} on line {} (byte {})\n{:w$} |\n{gutter} | {}\n{:w$} |",
+            e.at.line,
+            e.at.offset,
+            "",
+            e.snippet(),
+            "",
+            w = gutter.len()
+        )
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) | Self::Mmap(e) => Some(e),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,498 @@
+// Generating measurement files, like the official challenge's CreateMeasurements.java
+// Rows are generated in fixed-size batches, each with its own PRNG derived from the seed and the batch number,
+// so the output only depends on the seed and not on how many threads produced it
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    hash::{BuildHasher, Hasher},
+    io::{self, Write},
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    },
+};
+
+mod stations;
+
+use stations::STATIONS;
+
+use crate::{
+    fx_hash::{FxHasher, PI},
+    table,
+};
+
+/// Most stations a generated file can have, the maximum from the challenge rules
+pub const MAX_STATIONS: usize = crate::MAP_CAPACITY;
+/// Number of stations in the official list
+pub const OFFICIAL_STATIONS: usize = STATIONS.len();
+/// Longest station name allowed by the challenge rules, in bytes
+pub const MAX_NAME_LEN: usize = 100;
+
+const BATCH_ROWS: u64 = 64 * 1024;
+const BATCHES_PER_THREAD: usize = 2; // How many finished batches can be waiting to be written per thread
+const SPREAD: f64 = 10.0; // Standard deviation of the measurements around a station's mean, same as the official generator
+const COLLISION_BITS: u32 = 16; // Enough to put every name in the same bucket of a std table sized for 10,000 stations
+const CLUSTER_BITS: u32 = 10; // Starts every name within 16 slots of each other in the open table, so they share one probe run
+
+/// How long generated station names are
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameLengths {
+    /// The official station names, with made-up ones of similar lengths past the end of the list
+    #[default]
+    Official,
+    /// Made-up names of 1 to 4 bytes
+    Short,
+    /// Made-up names of 90 to 100 bytes, with plenty of multi-byte characters
+    Long,
+    /// Made-up names of anywhere between 1 and 100 bytes
+    Uniform,
+}
+
+/// How often each station comes up
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Frequencies {
+    /// Every station is as likely as any other, like the official generator
+    #[default]
+    Uniform,
+    /// The `n`th station is picked proportionally to `1 / n^s`, so a few stations get most of the rows
+    Zipf(f64),
+}
+
+/// What to generate
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Number of rows to write
+    pub rows: u64,
+    /// Number of distinct stations to pick rows from, up to [`MAX_STATIONS`]
+    pub stations: usize,
+    /// Seed for the PRNG, the same seed and config always give the same output
+    pub seed: u64,
+    /// Number of generating threads, `None` uses all available cores
+    pub threads: Option<NonZeroUsize>,
+    pub name_lengths: NameLengths,
+    pub frequencies: Frequencies,
+    /// Use made-up names crafted to collide, half of them in the std table's `FxHasher` buckets and half in the open
+    /// table's slots
+    pub collisions: bool,
+    /// Mix in rows that are valid but awkward: extreme and negative zero values, names that only differ at the very end
+    /// and names that end right at the length limit with a multi-byte character
+    pub adversarial: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            rows: 1_000_000_000,
+            stations: OFFICIAL_STATIONS,
+            seed: 0,
+            threads: None,
+            name_lengths: NameLengths::Official,
+            frequencies: Frequencies::Uniform,
+            collisions: false,
+            adversarial: false,
+        }
+    }
+}
+
+/// Named dataset shapes, for generating and benchmarking the cases that stress different parts of the implementation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// The official dataset, 413 stations picked uniformly
+    Classic,
+    /// The challenge's maximum of 10,000 unique stations
+    Unique10k,
+    /// Names of 90 to 100 bytes, mostly multi-byte UTF-8
+    LongNames,
+    /// 10,000 names crafted to collide, half in each of the station tables
+    Collisions,
+    /// Zipfian station frequencies, a handful of stations get most of the rows
+    Zipf,
+}
+
+impl Profile {
+    pub const ALL: [Self; 5] = [
+        Self::Classic,
+        Self::Unique10k,
+        Self::LongNames,
+        Self::Collisions,
+        Self::Zipf,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Classic => "classic",
+            Self::Unique10k => "10k",
+            Self::LongNames => "long-names",
+            Self::Collisions => "collisions",
+            Self::Zipf => "zipf",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.name() == name)
+    }
+
+    /// The generator config for this profile
+    pub fn config(self) -> Config {
+        let classic = Config::default();
+        match self {
+            Self::Classic => classic,
+            Self::Unique10k => Config {
+                stations: MAX_STATIONS,
+                ..classic
+            },
+            Self::LongNames => Config {
+                name_lengths: NameLengths::Long,
+                ..classic
+            },
+            Self::Collisions => Config {
+                stations: MAX_STATIONS,
+                collisions: true,
+                ..classic
+            },
+            Self::Zipf => Config {
+                frequencies: Frequencies::Zipf(1.0),
+                ..classic
+            },
+        }
+    }
+}
+
+/// SplitMix64, which is tiny, fast and plenty random for test data
+#[derive(Debug, Clone)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Uniform in `[0, 1)`
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Normally distributed with the given mean and standard deviation, using Box-Muller
+    pub fn gaussian(&mut self, mean: f64, sd: f64) -> f64 {
+        let u = 1.0 - self.unit();
+        let v = self.unit();
+        mean + sd * (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos()
+    }
+}
+
+struct Station {
+    name: String,
+    /// Mean temperature in tenths of a degree
+    mean: f64,
+}
+
+/// Letters for made-up names, a mix of one, two and three byte characters
+const LETTERS: [char; 32] = [
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'k', 'l', 'm', 'n', 'o', 'p', 'r', 's', 't', 'u',
+    'v', 'w', 'y', 'z', ' ', '-', 'é', 'ø', 'ü', 'ł', 'ș', 'ā', '京',
+];
+
+/// A made-up name of exactly `len` bytes
+fn made_up_name(rng: &mut Rng, len: usize) -> String {
+    let mut name = String::with_capacity(len);
+    // Capitalised and without surrounding whitespace, like a real name
+    name.push((b'A' + rng.below(26) as u8) as char);
+    while name.len() < len {
+        let c = LETTERS[rng.below(LETTERS.len())];
+        let c = if name.len() + c.len_utf8() > len || (c == ' ' && name.len() + 1 == len) {
+            (b'a' + rng.below(26) as u8) as char
+        } else {
+            c
+        };
+        name.push(c);
+    }
+    name
+}
+
+/// An adversarial name for the `i`th station, cycling through the awkward shapes
+fn adversarial_name(rng: &mut Rng, i: usize) -> String {
+    match i % 3 {
+        // Only the last few bytes differ, so comparing prefixes doesn't help
+        0 => format!("{}{:04}", "Abcdefgh".repeat(12), rng.below(10_000)),
+        // Right at the limit, with a three byte character ending exactly at the last byte
+        1 => made_up_name(rng, MAX_NAME_LEN - 3) + "京",
+        // As short as it gets
+        _ => {
+            let len = 1 + rng.below(2);
+            made_up_name(rng, len)
+        }
+    }
+}
+
+/// Made-up names for the std table that all end up with the same low [`COLLISION_BITS`] in their `FxHasher` hash
+///
+/// The hasher multiplies and then xors in each byte, so the low bits of its state only ever depend on the low bits
+/// of the previous state. That means the last two bytes of a name can be solved for instead of searched for.
+fn fx_colliding_name(rng: &mut Rng, target: u64) -> String {
+    let mask = (1 << COLLISION_BITS) - 1;
+    let target = target & mask;
+    loop {
+        let len = STATIONS[rng.below(STATIONS.len())].0.len().max(3) - 2;
+        let prefix = made_up_name(rng, len);
+        // The table keys are byte slices, which hash their length as a usize first and then every byte
+        let mut hasher = FxHasher::default();
+        hasher.write_usize(len + 2);
+        hasher.write(prefix.as_bytes());
+        let state = hasher.finish();
+
+        let first = b'a' + rng.below(26) as u8;
+        for b1 in (first..=b'z').chain(b'a'..first) {
+            let before_last = (state.wrapping_mul(PI) ^ b1 as u64).wrapping_mul(PI) & mask;
+            // The last byte can only fix the low 8 bits, the rest has to line up already
+            let b2 = before_last ^ target;
+            if (b'a' as u64..=b'z' as u64).contains(&b2) {
+                let name = format!("{prefix}{}{}", b1 as char, b2 as u8 as char);
+                debug_assert_eq!(FxHasher::default().hash_one(name.as_bytes()) & mask, target);
+                return name;
+            }
+        }
+    }
+}
+
+/// Made-up names for the open table whose hashes all share the top [`CLUSTER_BITS`] of `target`
+///
+/// The open table avalanches its hash so there's nothing to solve for, a random prefix just gets endings tried
+/// until one of them fits.
+fn slot_colliding_name(rng: &mut Rng, target: u64) -> String {
+    let cluster = |hash: u64| hash >> (u64::BITS - CLUSTER_BITS);
+    loop {
+        let len = STATIONS[rng.below(STATIONS.len())].0.len().max(4) - 3;
+        let mut name = made_up_name(rng, len).into_bytes();
+        name.extend_from_slice(b"aaa");
+        for ending in 0..26 * 26 * 26 {
+            name[len..].copy_from_slice(
+                &[ending / 676, ending / 26 % 26, ending % 26].map(|b| b'a' + b as u8),
+            );
+            if cluster(table::hash_name(&name)) == cluster(target) {
+                return String::from_utf8(name).expect("Made-up names are UTF-8");
+            }
+        }
+    }
+}
+
+/// Pick the stations for `config`, official ones first, then rename some of them if it's adversarial
+fn stations(config: &Config) -> Vec<Station> {
+    let mut rng = Rng::new(config.seed);
+    let mut stations = Vec::with_capacity(config.stations);
+    let mut names = HashSet::with_capacity(config.stations);
+
+    if config.name_lengths == NameLengths::Official && !config.collisions {
+        // Shuffle so asking for fewer stations than the list has gives a random selection
+        let mut official = STATIONS.to_vec();
+        for i in (1..official.len()).rev() {
+            official.swap(i, rng.below(i + 1));
+        }
+        for &(name, mean) in official.iter().take(config.stations) {
+            names.insert(name.to_string());
+            stations.push(Station {
+                name: name.to_string(),
+                mean: mean * 10.0,
+            });
+        }
+    }
+
+    let target = rng.next_u64();
+    let mut i = 0;
+    while stations.len() < config.stations {
+        let name = if config.collisions && i % 2 == 0 {
+            fx_colliding_name(&mut rng, target)
+        } else if config.collisions {
+            slot_colliding_name(&mut rng, target)
+        } else {
+            let len = match config.name_lengths {
+                NameLengths::Official => STATIONS[rng.below(STATIONS.len())].0.len(),
+                NameLengths::Short => 1 + rng.below(4),
+                NameLengths::Long => 90 + rng.below(11),
+                NameLengths::Uniform => 1 + rng.below(MAX_NAME_LEN),
+            };
+            made_up_name(&mut rng, len)
+        };
+        i += 1;
+
+        // Short names run out quickly, so just try again
+        if names.insert(name.clone()) {
+            // Somewhere around a real station's climate
+            let climate = STATIONS[rng.below(STATIONS.len())].1;
+            let mean = rng.gaussian(climate, 5.0);
+            stations.push(Station {
+                name,
+                mean: mean * 10.0,
+            });
+        }
+    }
+
+    if config.adversarial {
+        // Every fourth station gets an awkward name instead, whichever kind it was, taking the next shape when one
+        // has run out of names
+        let mut shape = 0;
+        for station in stations.iter_mut().step_by(4) {
+            station.name = loop {
+                let name = adversarial_name(&mut rng, shape);
+                shape += 1;
+                if names.insert(name.clone()) {
+                    break name;
+                }
+            };
+        }
+    }
+
+    stations
+}
+
+/// Picks which station each row is for
+enum Picker {
+    Uniform(usize),
+    /// Cumulative weights, ending in 1
+    Weighted(Vec<f64>),
+}
+
+impl Picker {
+    fn new(stations: usize, frequencies: Frequencies) -> Self {
+        match frequencies {
+            Frequencies::Uniform => Self::Uniform(stations),
+            Frequencies::Zipf(s) => {
+                let weights = (1..=stations).map(|n| (n as f64).powf(-s));
+                let total = weights.clone().sum::<f64>();
+                let mut sum = 0.0;
+                Self::Weighted(
+                    weights
+                        .map(|w| {
+                            sum += w / total;
+                            sum
+                        })
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    #[inline]
+    fn pick(&self, rng: &mut Rng) -> usize {
+        match self {
+            Self::Uniform(n) => rng.below(*n),
+            Self::Weighted(cumulative) => {
+                let x = rng.unit();
+                // Rounding can leave the last weight just short of 1
+                cumulative
+                    .partition_point(|&c| c <= x)
+                    .min(cumulative.len() - 1)
+            }
+        }
+    }
+}
+
+/// Write `tenths` with one decimal, without going through float formatting
+fn write_tenths(out: &mut Vec<u8>, tenths: i32) {
+    if tenths < 0 {
+        out.push(b'-');
+    }
+    let abs = tenths.unsigned_abs();
+    if abs >= 100 {
+        out.push(b'0' + (abs / 100) as u8);
+    }
+    out.extend_from_slice(&[b'0' + (abs / 10 % 10) as u8, b'.', b'0' + (abs % 10) as u8]);
+}
+
+/// Generate the `index`th batch of rows
+fn batch(stations: &[Station], picker: &Picker, config: &Config, index: u64) -> Vec<u8> {
+    // Seeding each batch from a generator keeps neighbouring batches' streams unrelated
+    let mut rng =
+        Rng::new(Rng::new(config.seed ^ index.wrapping_mul(0xA24B_AED4_963E_E407)).next_u64());
+    let rows = BATCH_ROWS.min(config.rows - index * BATCH_ROWS);
+    let mut out = Vec::with_capacity(rows as usize * 16);
+
+    for _ in 0..rows {
+        let station = &stations[picker.pick(&mut rng)];
+        out.extend_from_slice(station.name.as_bytes());
+        out.push(b';');
+
+        if config.adversarial && rng.below(8) == 0 {
+            const AWKWARD: [&str; 8] =
+                ["-99.9", "99.9", "-0.0", "0.0", "-0.1", "0.1", "-9.9", "9.9"];
+            out.extend_from_slice(AWKWARD[rng.below(AWKWARD.len())].as_bytes());
+        } else {
+            let tenths = rng.gaussian(station.mean, SPREAD * 10.0).round() as i32;
+            write_tenths(&mut out, tenths.clamp(-999, 999));
+        }
+        out.push(b'\n');
+    }
+
+    out
+}
+
+/// Write `config.rows` rows of measurements to `writer`
+pub fn generate(mut writer: impl Write, config: &Config) -> io::Result<()> {
+    if !(1..=MAX_STATIONS).contains(&config.stations) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("number of stations must be between 1 and {MAX_STATIONS}"),
+        ));
+    }
+    if matches!(config.frequencies, Frequencies::Zipf(s) if !(s.is_finite() && s >= 0.0)) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Zipf exponent must be a finite, non-negative number",
+        ));
+    }
+
+    let num_threads = config
+        .threads
+        .unwrap_or_else(|| {
+            std::thread::available_parallelism().expect("Error getting number of threads")
+        })
+        .get();
+    let stations = stations(config);
+    let picker = Picker::new(stations.len(), config.frequencies);
+    let batches = config.rows.div_ceil(BATCH_ROWS);
+    let next_batch = AtomicU64::new(0);
+
+    std::thread::scope(|s| {
+        let (tx, rx) = mpsc::sync_channel(num_threads * BATCHES_PER_THREAD);
+        for _ in 0..num_threads {
+            let tx = tx.clone();
+            let (stations, picker, next_batch) = (&stations, &picker, &next_batch);
+            s.spawn(move || loop {
+                let index = next_batch.fetch_add(1, Ordering::Relaxed);
+                if index >= batches
+                    || tx
+                        .send((index, batch(stations, picker, config, index)))
+                        .is_err()
+                {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        // Batches finish in any order, hold on to the early ones until it's their turn
+        let mut pending = BTreeMap::new();
+        let mut next_write = 0;
+        for (index, data) in rx {
+            pending.insert(index, data);
+            while let Some(data) = pending.remove(&next_write) {
+                writer.write_all(&data)?;
+                next_write += 1;
+            }
+        }
+        writer.flush()
+    })
+}
@@ -0,0 +1,1096 @@
+use std::{
+    borrow::Cow,
+    collections::{hash_map::Entry, HashMap},
+    fs::File,
+    hash::{BuildHasher, Hash},
+    num::NonZeroUsize,
+    ops::Range,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use affinity::Placement;
+use error::count_lines;
+use fx_hash::FxHasher;
+use mmap::Input;
+use scan::{Classify, Delimiters};
+use table::{Added, StationTable};
+
+// eprintln! that only prints if the options ask for it
+macro_rules! log {
+    ($opts:expr, $($arg:tt)*) => {
+        if $opts.verbose {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+pub mod affinity;
+mod error;
+pub mod generate;
+pub mod mmap;
+pub mod names;
+mod scan;
+mod stream;
+mod table;
+pub mod temperature;
+mod utf8;
+
+pub use error::{Error, ErrorPolicy, LineError, Location, Rejects, Result};
+pub use names::{Collation, Normalization};
+pub use scan::Scanner;
+pub use stream::aggregate_reader;
+pub use temperature::{ExcessPrecision, MAX_DECIMALS};
+
+type RowMap<K> = HashMap<K, StationStats, FxHasher>;
+
+/// Type of [`StationStats::sum`], the same whichever features are enabled
+pub type Sum = i128;
+
+/// What the sums are kept in while aggregating, wide enough that only absurdly large inputs can overflow it
+#[cfg(not(feature = "i128-sum"))]
+type Acc = i64;
+/// What the sums are kept in while aggregating, wide enough that no input can realistically overflow it
+#[cfg(feature = "i128-sum")]
+type Acc = i128;
+
+const MAP_CAPACITY: usize = 10_000; // Taken from the problem description, "There is a maximum of 10,000 unique station names."
+
+/// Small enough that a thread that falls behind only holds everyone up for a few ms, big enough that taking one is noise
+pub const DEFAULT_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Enough bad lines to see what's wrong with a file, without holding on to all of a badly broken one
+pub const DEFAULT_MAX_REJECTS: usize = 10_000;
+
+/// Options controlling how the input is processed
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Number of worker threads, `None` uses all available cores
+    pub threads: Option<NonZeroUsize>,
+    /// Separator between the station name and the temperature
+    pub separator: char,
+    /// How the bytes of station names are turned into text
+    pub encoding: Encoding,
+    /// How station names are cleaned up once they're text
+    pub normalization: Normalization,
+    /// Digits after the decimal point measurements are kept with, from 1 to [`MAX_DECIMALS`]
+    pub decimals: u8,
+    /// What to do with measurements that have more digits than `decimals`
+    pub excess_precision: ExcessPrecision,
+    /// Read files in blocks instead of mapping them, for files that don't fit in the address space
+    pub stream: bool,
+    /// Bytes of a mapped file the worker threads take at a time, smaller chunks even out the work between them
+    pub chunk_size: usize,
+    /// What to do with lines that can't be parsed
+    pub on_error: ErrorPolicy,
+    /// Most bad lines to keep with [`ErrorPolicy::Report`], the rest are only counted
+    pub max_rejects: usize,
+    /// Fail with [`Error::Overflow`] if a sum overflows instead of wrapping around (debug builds always panic)
+    pub checked: bool,
+    /// Hash table the worker threads collect stations in
+    pub table: Table,
+    /// How lines are searched for separators and newlines, one the CPU doesn't support falls back to [`Scanner::Swar`]
+    pub scanner: Scanner,
+    /// Pin every worker thread to a CPU, spreading them over the NUMA nodes, and have the threads on each node
+    /// start on their own part of a mapped file
+    pub pin: bool,
+    /// Print logs and timings to stderr
+    pub verbose: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            threads: None,
+            separator: ';',
+            encoding: Encoding::Utf8,
+            normalization: Normalization::default(),
+            decimals: 1,
+            excess_precision: ExcessPrecision::Round,
+            stream: false,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            on_error: ErrorPolicy::Fail,
+            max_rejects: DEFAULT_MAX_REJECTS,
+            checked: false,
+            table: Table::Open,
+            scanner: Scanner::Auto,
+            pin: false,
+            verbose: false,
+        }
+    }
+}
+
+/// Hash table used by the worker threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Table {
+    /// Fixed-capacity open addressing keyed by the name bytes, hashed a word at a time
+    Open,
+    /// A `HashMap` keyed by the name bytes with the byte-at-a-time FxHasher, to compare against
+    Std,
+}
+
+impl Table {
+    pub const ALL: [Self; 2] = [Self::Open, Self::Std];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Std => "std",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.name() == name)
+    }
+}
+
+/// Character encoding of the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Names have to be valid UTF-8, lines with names that aren't are bad lines
+    Utf8,
+    /// Every byte is a character, U+0000 to U+00FF, and gets transcoded to UTF-8
+    Latin1,
+    /// Names are opaque bytes, only turned into text for display with invalid UTF-8 escaped as `\xFC`
+    ///
+    /// Names that are valid UTF-8 are shown as they are. Backslashes in the ones that aren't are escaped as `\\`,
+    /// so two invalid names never look alike, but a valid name that spells out an escape looks like, and is
+    /// counted as, the invalid one it spells.
+    Bytes,
+    /// Names are opaque bytes, only turned into text for display with invalid UTF-8 replaced by U+FFFD
+    ///
+    /// Names that only differ in their invalid bytes display the same, and are merged into one station.
+    Lossy,
+}
+
+impl Encoding {
+    pub const ALL: [Self; 4] = [Self::Utf8, Self::Latin1, Self::Bytes, Self::Lossy];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf8",
+            Self::Latin1 => "latin1",
+            Self::Bytes => "bytes",
+            Self::Lossy => "lossy",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.name() == name)
+    }
+
+    /// Whether `name` can be a station name, checked once per thread when a station is first seen
+    #[inline]
+    fn accepts(self, name: &[u8]) -> bool {
+        self != Self::Utf8 || utf8::is_valid(name)
+    }
+
+    /// The text of a station name
+    fn decode(self, name: &[u8]) -> Cow<'_, str> {
+        match self {
+            // Already validated with utf8, so never actually lossy
+            Self::Utf8 | Self::Lossy => String::from_utf8_lossy(name),
+            Self::Latin1 => name.iter().map(|&b| b as char).collect(),
+            Self::Bytes => match std::str::from_utf8(name) {
+                Ok(text) => Cow::Borrowed(text),
+                Err(_) => Cow::Owned(escape_invalid(name)),
+            },
+        }
+    }
+
+    /// How `c` is written in this encoding, UTF-8 for characters Latin-1 doesn't have
+    fn encode(self, c: char) -> Vec<u8> {
+        match self {
+            Self::Latin1 if (c as u32) <= 0xFF => vec![c as u8],
+            _ => c.to_string().into_bytes(),
+        }
+    }
+}
+
+/// `name` with its invalid UTF-8 bytes written as `\xFC` and backslashes as `\\`, so it can be told apart from
+/// any other invalid name
+fn escape_invalid(name: &[u8]) -> String {
+    let mut text = String::with_capacity(name.len() + 8);
+    for chunk in name.utf8_chunks() {
+        text.push_str(&chunk.valid().replace('\\', "\\\\"));
+        for byte in chunk.invalid() {
+            text.push_str(&format!("\\x{byte:02X}"));
+        }
+    }
+    text
+}
+
+impl Options {
+    fn num_threads(&self) -> usize {
+        self.threads
+            .unwrap_or_else(|| {
+                std::thread::available_parallelism().expect("Error getting number of threads")
+            })
+            .get()
+    }
+
+    /// Where each of `threads` worker threads goes, nowhere in particular unless [`pin`](Self::pin) is set
+    fn placement(&self, threads: usize) -> Result<Vec<Placement>> {
+        if !self.pin {
+            return Ok(Vec::new());
+        }
+        Ok(affinity::Topology::detect()?.place(threads))
+    }
+}
+
+/// Aggregated measurements for a single station
+///
+/// Values are fixed-point with [`Options::decimals`] digits after the point, so tenths of a degree by default.
+/// Build one with [`new`](Self::new) and [`add`](Self::add), features can add fields of their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StationStats {
+    pub min: i32,
+    pub max: i32,
+    /// Read with [`sum`](Self::sum), it's only an i128 with the `i128-sum` feature
+    sum: Acc,
+    pub count: u64,
+    /// Always an i128: at 4 decimals a single square is up to 10^12, so an i64 would overflow within 10 million rows
+    #[cfg(feature = "stddev")]
+    sum_squares: i128,
+}
+
+impl StationStats {
+    #[inline]
+    pub fn new(temp: i32) -> Self {
+        Self {
+            min: temp,
+            max: temp,
+            sum: temp as Acc,
+            count: 1,
+            #[cfg(feature = "stddev")]
+            sum_squares: square(temp),
+        }
+    }
+
+    #[inline]
+    pub fn add(&mut self, temp: i32) {
+        if temp < self.min {
+            self.min = temp;
+        } else if temp > self.max {
+            self.max = temp;
+        }
+        self.sum += temp as Acc;
+        self.count += 1;
+        #[cfg(feature = "stddev")]
+        {
+            self.sum_squares += square(temp);
+        }
+    }
+
+    /// Like [`add`](Self::add), but `None` if the sum would overflow
+    #[inline]
+    pub fn checked_add(mut self, temp: i32) -> Option<Self> {
+        self.sum.checked_add(temp as Acc)?;
+        #[cfg(feature = "stddev")]
+        self.sum_squares.checked_add(square(temp))?;
+        self.add(temp);
+        Some(self)
+    }
+
+    /// Mean of the measurements, in the same fixed-point units
+    ///
+    /// Rounded half towards positive infinity like the reference implementation (`Math.round`), so 14.65 is 14.7 and -14.65 is -14.6.
+    #[inline]
+    pub fn mean(&self) -> i32 {
+        let count = self.count as Acc;
+        // Euclidean division floors even for negative sums, so only the remainder decides the rounding
+        let (q, r) = (self.sum.div_euclid(count), self.sum.rem_euclid(count));
+        (q + (r >= count - r) as Acc) as i32
+    }
+
+    /// Sum of the measurements, in the same fixed-point units
+    #[inline]
+    pub fn sum(&self) -> Sum {
+        Sum::from(self.sum)
+    }
+
+    /// Sum of the squares of the measurements, for [`stddev`](Self::stddev)
+    #[cfg(feature = "stddev")]
+    #[inline]
+    pub fn sum_squares(&self) -> i128 {
+        self.sum_squares
+    }
+
+    /// Population standard deviation of the measurements, in the same fixed-point units and rounded to the nearest
+    #[cfg(feature = "stddev")]
+    pub fn stddev(&self) -> i32 {
+        // n² times the variance is exact in integers, only the square root needs floating point
+        let (n, sum) = (self.count as i128, self.sum());
+        let spread = n * self.sum_squares - sum * sum;
+        ((spread.max(0) as f64).sqrt() / self.count as f64).round() as i32
+    }
+
+    /// Combine the measurements of `other` into `self`
+    #[inline]
+    pub fn merge(&mut self, other: &Self) {
+        // Unlike a single measurement, another thread's partial stats can lower the min and raise the max at once
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.count += other.count;
+        #[cfg(feature = "stddev")]
+        {
+            self.sum_squares += other.sum_squares;
+        }
+    }
+
+    /// Like [`merge`](Self::merge), but `None` if the sum or count would overflow
+    #[inline]
+    pub fn checked_merge(mut self, other: &Self) -> Option<Self> {
+        self.sum.checked_add(other.sum)?;
+        self.count.checked_add(other.count)?;
+        #[cfg(feature = "stddev")]
+        self.sum_squares.checked_add(other.sum_squares)?;
+        self.merge(other);
+        Some(self)
+    }
+}
+
+/// `temp` squared, for [`StationStats::sum_squares`]
+#[cfg(feature = "stddev")]
+#[inline]
+fn square(temp: i32) -> i128 {
+    (i64::from(temp) * i64::from(temp)).into()
+}
+
+/// Add a measurement to `stats`, returning `false` if checking is enabled and the sum overflowed
+#[inline]
+fn accumulate(stats: &mut StationStats, temp: i32, checked: bool) -> bool {
+    if !checked {
+        stats.add(temp);
+        return true;
+    }
+    match stats.checked_add(temp) {
+        Some(s) => {
+            *stats = s;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Merge the per-thread map `b` into `a`
+fn merge_row_maps<K: Eq + Hash + AsRef<[u8]>, S: BuildHasher>(
+    a: &mut HashMap<K, StationStats, S>,
+    b: HashMap<K, StationStats, S>,
+    checked: bool,
+) -> Result<()> {
+    for (k, v) in b {
+        match a.entry(k) {
+            Entry::Occupied(mut entry) if checked => match entry.get().checked_merge(&v) {
+                Some(merged) => *entry.get_mut() = merged,
+                None => {
+                    let name = String::from_utf8_lossy(entry.key().as_ref()).into_owned();
+                    return Err(Error::Overflow(name));
+                }
+            },
+            Entry::Occupied(mut entry) => entry.get_mut().merge(&v),
+            Entry::Vacant(entry) => {
+                entry.insert(v);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The stations one worker thread has seen, in the table [`Options::table`] asked for
+enum PartialStations<K> {
+    Std(RowMap<K>),
+    Open(StationTable),
+}
+
+impl<K: Eq + Hash + AsRef<[u8]>> PartialStations<K> {
+    fn new(opts: &Options, map_capacity: usize) -> Self {
+        match opts.table {
+            Table::Open => Self::Open(StationTable::new()),
+            Table::Std => Self::Std(RowMap::with_capacity_and_hasher(
+                map_capacity,
+                FxHasher::default(),
+            )),
+        }
+    }
+
+    fn merge(&mut self, other: Self, checked: bool) -> Result<()> {
+        match (self, other) {
+            (Self::Std(a), Self::Std(b)) => merge_row_maps(a, b, checked),
+            (Self::Open(a), Self::Open(b)) => a.merge(b, checked),
+            _ => unreachable!("every thread uses the same table"),
+        }
+    }
+
+    /// The stations by their decoded and normalized names, merging the ones that end up with the same name
+    fn into_stations(self, opts: &Options) -> Result<HashMap<String, StationStats>> {
+        let mut stations = HashMap::<String, StationStats>::with_capacity(MAP_CAPACITY);
+        let mut add = |name: &[u8], stats: &StationStats| {
+            let name = opts.encoding.decode(name);
+            let name = opts.normalization.apply(&name).into_owned();
+            match stations.entry(name) {
+                Entry::Occupied(mut entry) if opts.checked => {
+                    match entry.get().checked_merge(stats) {
+                        Some(merged) => *entry.get_mut() = merged,
+                        None => return Err(Error::Overflow(entry.key().clone())),
+                    }
+                }
+                Entry::Occupied(mut entry) => entry.get_mut().merge(stats),
+                Entry::Vacant(entry) => {
+                    entry.insert(*stats);
+                }
+            }
+            Ok(())
+        };
+        match &self {
+            Self::Std(map) => map
+                .iter()
+                .try_for_each(|(name, stats)| add(name.as_ref(), stats))?,
+            Self::Open(table) => table
+                .entries()
+                .try_for_each(|(name, stats)| add(name, stats))?,
+        }
+        Ok(stations)
+    }
+}
+
+/// Merge the stations of every thread into one, pairwise and in parallel
+///
+/// Each round merges the second half of `partials` into the first half on as many threads, so `n` threads' worth
+/// of stations take `log2(n)` rounds instead of `n - 1` merges one after another.
+fn merge_tree<K: Eq + Hash + AsRef<[u8]> + Send>(
+    mut partials: Vec<PartialStations<K>>,
+    checked: bool,
+) -> Result<PartialStations<K>> {
+    while partials.len() > 1 {
+        let others = partials.split_off(partials.len().div_ceil(2));
+        std::thread::scope(|s| {
+            let mut pairs = partials.iter_mut().zip(others);
+            // The calling thread would only be waiting anyway, so it takes one of the pairs itself
+            let (own, own_other) = pairs.next().expect("there are at least two partials");
+            let handles = pairs
+                .map(|(partial, other)| s.spawn(move || partial.merge(other, checked)))
+                .collect::<Vec<_>>();
+            let own = own.merge(own_other, checked);
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("Error joining merge thread"))
+                .fold(own, Result::and)
+        })?;
+    }
+    Ok(partials.pop().expect("Error reducing threads"))
+}
+
+/// How long each phase of aggregating took, for benchmarking
+#[derive(Debug, Clone, Default)]
+pub struct Timings {
+    /// Opening and mapping (or reading) the file, zero when streaming
+    pub map: Duration,
+    /// Handing out chunks and lining them up with lines, by the thread that spent the longest on it
+    ///
+    /// Threads do this in between parsing, so it's also part of [`Timings::parse`]. Zero when streaming.
+    pub chunk: Duration,
+    /// Parsing and aggregating, until the slowest thread finished
+    pub parse: Duration,
+    /// Merging the per-thread results
+    pub merge: Duration,
+    /// Per worker thread, in the order they were started
+    pub threads: Vec<ThreadTimings>,
+}
+
+/// What a single worker thread got through
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadTimings {
+    /// Lines processed, including bad ones
+    pub rows: u64,
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+/// Result of aggregating a measurements file
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub stations: HashMap<String, StationStats>,
+    /// Digits after the decimal point in the [`StationStats`] values
+    pub decimals: u8,
+    /// Lines that were skipped, always empty with [`ErrorPolicy::Fail`]
+    pub rejects: Rejects,
+    pub timings: Timings,
+    /// CPU and NUMA node of every worker thread, in the order they were started, empty unless [`Options::pin`]
+    pub placement: Vec<Placement>,
+}
+
+impl Stats {
+    /// Stations sorted by name
+    pub fn sorted(&self) -> Vec<(&str, &StationStats)> {
+        self.sorted_by(Collation::Codepoint)
+    }
+
+    /// Stations sorted by name, in the order of `collation`
+    pub fn sorted_by(&self, collation: Collation) -> Vec<(&str, &StationStats)> {
+        let mut entries = self
+            .stations
+            .iter()
+            .map(|(name, stats)| (name.as_str(), stats))
+            .collect::<Vec<_>>();
+        match collation {
+            Collation::Codepoint => entries.sort_unstable_by(|a, b| a.0.cmp(b.0)),
+            // Names that collate the same still need an order, so it doesn't change from run to run
+            Collation::Unicode => {
+                entries.sort_by_cached_key(|&(name, _)| (names::CollationKey::new(name), name))
+            }
+        }
+        entries
+    }
+}
+
+/// Memory map the file at `path` and aggregate it using all worker threads
+///
+/// Pipes, sockets and other special files are streamed instead, as are regular files if [`Options::stream`] is set.
+pub fn aggregate_file(path: impl AsRef<Path>, opts: &Options) -> Result<Stats> {
+    let instant = Instant::now();
+
+    let mut file = File::open(path)?;
+
+    if opts.stream || !file.metadata()?.file_type().is_file() {
+        return aggregate_reader(file, opts);
+    }
+
+    let input = Input::open(&mut file)?;
+
+    // We don't need the file handle anymore
+    drop(file);
+
+    match &input {
+        Input::Mapped(map) => log!(
+            opts,
+            "Total range: 0x{:x} - 0x{:x}",
+            map.addr(),
+            map.addr() + map.len() - 1
+        ),
+        Input::Buffered(buf) => log!(
+            opts,
+            "File can't be mapped, read {} bytes into memory",
+            buf.len()
+        ),
+    }
+
+    let map = instant.elapsed();
+    log!(opts, "====== Mapping took {} ms ======", map.as_millis());
+
+    let mut stats = aggregate_mapped(&input, opts)?;
+    stats.timings.map = map;
+    Ok(stats)
+}
+
+/// Aggregate measurements that are already in memory
+pub fn aggregate_bytes(bytes: &[u8], opts: &Options) -> Result<Stats> {
+    aggregate_mapped(bytes, opts)
+}
+
+/// Parse every line in `slice` and hand the station name, its [`table::hash_name`] and the temperature to `f`
+///
+/// `f` returns `false` if it refused a new station because its name isn't valid in [`Options::encoding`], which makes
+/// it a bad line. Bad lines are handled according to [`Options::on_error`], errors and rejects point at lines
+/// relative to the start of `slice`. Returns the number of lines, bad ones included.
+#[inline]
+fn for_each_row<'a>(
+    slice: &'a [u8],
+    opts: &Options,
+    rejects: &mut Rejects,
+    f: impl FnMut(&'a [u8], u64, i32) -> bool,
+) -> Result<u64> {
+    let separator = opts.encoding.encode(opts.separator);
+    if !scan::can_scan_for(&separator) {
+        return for_each_line(slice, &separator, opts, rejects, f);
+    }
+    match opts.scanner.resolve() {
+        #[cfg(target_arch = "x86_64")]
+        Scanner::Avx2 => scan_rows::<scan::Avx2>(slice, separator[0], opts, rejects, f),
+        #[cfg(target_arch = "x86_64")]
+        Scanner::Sse2 => scan_rows::<scan::Sse2>(slice, separator[0], opts, rejects, f),
+        #[cfg(target_arch = "aarch64")]
+        Scanner::Neon => scan_rows::<scan::Neon>(slice, separator[0], opts, rejects, f),
+        _ => scan_rows::<scan::Swar>(slice, separator[0], opts, rejects, f),
+    }
+}
+
+/// [`for_each_row`] going from one separator or newline to the next, as found by `C`
+#[inline]
+fn scan_rows<'a, C: Classify>(
+    bytes: &'a [u8],
+    separator: u8,
+    opts: &Options,
+    rejects: &mut Rejects,
+    mut f: impl FnMut(&'a [u8], u64, i32) -> bool,
+) -> Result<u64> {
+    let mut delimiters = Delimiters::<C>::new(bytes, separator);
+
+    let mut rows = 0;
+    let mut start = 0;
+    while start < bytes.len() {
+        rows += 1;
+        let (delimiter, hash) = delimiters.next_delimiter_hashed(start);
+        let (name_end, line_end) = match delimiter {
+            Some((i, false)) => (Some(i), delimiters.next_newline().unwrap_or(bytes.len())),
+            Some((i, true)) => (None, i),
+            None => (None, bytes.len()),
+        };
+        // Same as `str::lines`, a carriage return before the newline isn't part of the line
+        let content_end =
+            if line_end < bytes.len() && line_end > start && bytes[line_end - 1] == b'\r' {
+                line_end - 1
+            } else {
+                line_end
+            };
+        let line = start..content_end;
+        let line_number = rows as usize;
+        start = line_end + 1;
+
+        let bad_line = || LineError::new(line.start, line_number, &bytes[line.clone()]);
+
+        let Some(name_end) = name_end.filter(|&i| i < content_end) else {
+            rejects.reject(Error::MissingSeparator(bad_line()), opts)?;
+            continue;
+        };
+        let temp = match temperature::parse_fixed(
+            &bytes[name_end + 1..content_end],
+            opts.decimals,
+            opts.excess_precision,
+        ) {
+            Some(temp) => temp,
+            None => {
+                rejects.reject(Error::BadTemperature(bad_line()), opts)?;
+                continue;
+            }
+        };
+
+        if !f(&bytes[line.start..name_end], hash, temp) {
+            rejects.reject(Error::InvalidUtf8(bad_line()), opts)?;
+        }
+    }
+    Ok(rows)
+}
+
+/// [`for_each_row`] for separators the scanners can't look for, one line at a time
+fn for_each_line<'a>(
+    slice: &'a [u8],
+    separator: &[u8],
+    opts: &Options,
+    rejects: &mut Rejects,
+    mut f: impl FnMut(&'a [u8], u64, i32) -> bool,
+) -> Result<u64> {
+    let mut rows = 0;
+    let mut start = 0;
+    while start < slice.len() {
+        rows += 1;
+        let line_end = slice[start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(slice.len(), |i| start + i);
+        // Like `scan_rows`, a carriage return before the newline isn't part of the line
+        let content_end =
+            if line_end < slice.len() && line_end > start && slice[line_end - 1] == b'\r' {
+                line_end - 1
+            } else {
+                line_end
+            };
+        let line = &slice[start..content_end];
+        let line_start = start;
+        start = line_end + 1;
+
+        let bad_line = || LineError::new(line_start, rows as usize, line);
+
+        let Some(name_end) = line.windows(separator.len()).position(|w| w == separator) else {
+            rejects.reject(Error::MissingSeparator(bad_line()), opts)?;
+            continue;
+        };
+        let temp = match temperature::parse_fixed(
+            &line[name_end + separator.len()..],
+            opts.decimals,
+            opts.excess_precision,
+        ) {
+            Some(temp) => temp,
+            None => {
+                rejects.reject(Error::BadTemperature(bad_line()), opts)?;
+                continue;
+            }
+        };
+
+        let name = &line[..name_end];
+        if !f(name, table::hash_name(name), temp) {
+            rejects.reject(Error::InvalidUtf8(bad_line()), opts)?;
+        }
+    }
+    Ok(rows)
+}
+
+/// Aggregate every line of `slice` into `stations`, returning the rejects and number of lines
+fn aggregate_slice<'a>(
+    stations: &mut PartialStations<&'a [u8]>,
+    slice: &'a [u8],
+    opts: &Options,
+) -> Result<(Rejects, u64)> {
+    let mut rejects = Rejects::default();
+    let mut overflowed = None;
+    let rows = match stations {
+        PartialStations::Std(row_map) => {
+            for_each_row(slice, opts, &mut rejects, |name, _, temp| {
+                match row_map.entry(name) {
+                    Entry::Occupied(mut entry) => {
+                        if !accumulate(entry.get_mut(), temp, opts.checked) {
+                            overflowed.get_or_insert(name);
+                        }
+                    }
+                    Entry::Vacant(_) if !opts.encoding.accepts(name) => return false,
+                    Entry::Vacant(entry) => {
+                        entry.insert(StationStats::new(temp));
+                    }
+                }
+                true
+            })?
+        }
+        PartialStations::Open(table) => {
+            for_each_row(slice, opts, &mut rejects, |name, hash, temp| {
+                match table.add(name, hash, temp, opts.checked, |name| {
+                    opts.encoding.accepts(name)
+                }) {
+                    Added::Counted => true,
+                    Added::Overflowed => {
+                        overflowed.get_or_insert(name);
+                        true
+                    }
+                    Added::Refused => false,
+                }
+            })?
+        }
+    };
+    if let Some(name) = overflowed {
+        return Err(Error::Overflow(opts.encoding.decode(name).into_owned()));
+    }
+    Ok((rejects, rows))
+}
+
+/// Start of the first line at or after `at`, the lines starting in a chunk are the ones it owns
+fn line_start(bytes: &[u8], at: usize) -> usize {
+    if at == 0 || at >= bytes.len() {
+        return at.min(bytes.len());
+    }
+    bytes[at - 1..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(bytes.len(), |i| at + i)
+}
+
+/// Pin worker thread `t` to where `placement` says it goes, if anywhere
+fn start_worker(t: usize, placement: &[Placement], opts: &Options) -> Result<()> {
+    match placement.get(t) {
+        Some(p) => {
+            affinity::pin_current_thread(p.cpu)?;
+            log!(
+                opts,
+                "Thread {} started on CPU {} (node {})",
+                t + 1,
+                p.cpu,
+                p.node
+            );
+        }
+        None => log!(opts, "Thread {} started", t + 1),
+    }
+    Ok(())
+}
+
+/// Part of a mapped file, handed out a chunk at a time
+struct Region {
+    /// Start of the next chunk
+    cursor: AtomicUsize,
+    end: usize,
+}
+
+/// Split `len` bytes into one region per NUMA node in `placement`, sized by how many threads are on the node
+///
+/// Returns the regions along with the region of every thread. Each thread takes chunks from its own node's region
+/// until there are none left and only then helps out with the others, so when the file isn't cached yet its
+/// pages get read in, and allocated, by the node that goes on to use them.
+fn node_regions(len: usize, placement: &[Placement]) -> (Vec<Region>, Vec<usize>) {
+    let mut nodes = placement.iter().map(|p| p.node).collect::<Vec<_>>();
+    nodes.sort_unstable();
+    nodes.dedup();
+    if nodes.len() <= 1 {
+        let region = Region {
+            cursor: AtomicUsize::new(0),
+            end: len,
+        };
+        return (vec![region], vec![0; placement.len()]);
+    }
+
+    let mut regions = Vec::with_capacity(nodes.len());
+    let mut start = 0;
+    let mut threads_before = 0;
+    for &node in &nodes {
+        threads_before += placement.iter().filter(|p| p.node == node).count();
+        let end = (len as u128 * threads_before as u128 / placement.len() as u128) as usize;
+        regions.push(Region {
+            cursor: AtomicUsize::new(start),
+            end,
+        });
+        start = end;
+    }
+    let homes = placement
+        .iter()
+        .map(|p| nodes.binary_search(&p.node).unwrap())
+        .collect();
+    (regions, homes)
+}
+
+/// What a worker thread got out of the chunks it took
+struct Worker<'a> {
+    stations: PartialStations<&'a [u8]>,
+    /// Rejects of every chunk that had some, with line numbers relative to the chunk starting at that offset
+    rejects: Vec<(usize, Rejects)>,
+    chunks: u64,
+    /// Time spent claiming chunks and finding where their lines start
+    chunking: Duration,
+    timings: ThreadTimings,
+}
+
+/// Claim the next chunk of `region`, as the lines starting in it, or `None` once the region is used up
+#[inline]
+fn claim_chunk(bytes: &[u8], region: &Region, chunk_size: usize) -> Option<Range<usize>> {
+    // Never past the end, a plain `fetch_add` of a huge chunk size from every thread could wrap back around to 0
+    let end = |at: usize| at.saturating_add(chunk_size).min(region.end);
+    let at = region
+        .cursor
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |at| {
+            (at < region.end).then(|| end(at))
+        })
+        .ok()?;
+    Some(line_start(bytes, at)..line_start(bytes, end(at)))
+}
+
+/// Keep taking the next chunk of `input` and aggregating it until there are none left, from region `home` first
+fn aggregate_chunks<'a>(
+    bytes: &'a [u8],
+    regions: &[Region],
+    home: usize,
+    opts: &Options,
+    map_capacity: usize,
+) -> Result<Worker<'a>> {
+    let instant = Instant::now();
+    let chunk_size = opts.chunk_size.max(1);
+
+    let mut worker = Worker {
+        stations: PartialStations::new(opts, map_capacity),
+        rejects: Vec::new(),
+        chunks: 0,
+        chunking: Duration::ZERO,
+        timings: ThreadTimings::default(),
+    };
+    for r in (0..regions.len()).map(|i| (home + i) % regions.len()) {
+        let region = &regions[r];
+        // Chunks in a region are taken in order, so keeping this thread's first few rejects in each region keeps the
+        // first few overall
+        let mut kept_budget = opts.max_rejects;
+        loop {
+            let claimed = Instant::now();
+            let chunk = claim_chunk(bytes, region, chunk_size);
+            worker.chunking += claimed.elapsed();
+            let Some(Range { start, end }) = chunk else {
+                break;
+            };
+            // No line starts in here, it's all the middle of a line longer than a chunk
+            if start >= end {
+                continue;
+            }
+
+            match aggregate_slice(&mut worker.stations, &bytes[start..end], opts) {
+                Ok((mut rejects, rows)) => {
+                    if rejects.total() > 0 {
+                        rejects.keep_within(&mut kept_budget);
+                        worker.rejects.push((start, rejects));
+                    }
+                    worker.timings.rows += rows;
+                    worker.timings.bytes += (end - start) as u64;
+                    worker.chunks += 1;
+                }
+                Err(e) => {
+                    // Every chunk before this one in its region has been handed out already, and earlier regions
+                    // keep going, so stopping only the rest of the file still finds the first bad line
+                    for later in &regions[r..] {
+                        later.cursor.fetch_max(later.end, Ordering::Relaxed);
+                    }
+                    return Err(e.relocate(start, count_lines(&bytes[..start])));
+                }
+            }
+        }
+    }
+
+    worker.timings.elapsed = instant.elapsed();
+    Ok(worker)
+}
+
+fn aggregate_mapped(input: &[u8], opts: &Options) -> Result<Stats> {
+    let instant = Instant::now();
+
+    let num_threads = opts.num_threads();
+    let map_capacity = MAP_CAPACITY / num_threads;
+    let placement = opts.placement(num_threads)?;
+    let (regions, homes) = node_regions(input.len(), &placement);
+
+    log!(
+        opts,
+        "Splitting {} bytes into {} KiB chunks for {num_threads} threads",
+        input.len(),
+        opts.chunk_size / 1024
+    );
+
+    let results = std::thread::scope(|s| {
+        let handles = (0..num_threads)
+            .map(|t| {
+                let (regions, placement) = (&regions, &placement);
+                let home = homes.get(t).copied().unwrap_or(0);
+                s.spawn(move || {
+                    start_worker(t, placement, opts)?;
+                    aggregate_chunks(input, regions, home, opts, map_capacity)
+                })
+            })
+            .collect::<Vec<_>>(); // Need to collect to wait for threads to finish
+
+        handles
+            .into_iter()
+            .enumerate()
+            .map(|(i, t)| {
+                let r = t.join().expect("Error joining thread");
+                if let Ok(worker) = &r {
+                    log!(
+                        opts,
+                        "Thread {} finished after {} chunks",
+                        i + 1,
+                        worker.chunks
+                    );
+                }
+                r
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let parse = instant.elapsed();
+    let instant = Instant::now();
+
+    // Chunks are handed out in order but finish in any order, so report the bad line that comes first in the input
+    let offset = |e: &Error| e.line_error().map(|e| e.at.offset);
+    let mut first_error: Option<Error> = None;
+    let mut workers = Vec::with_capacity(results.len());
+    for r in results {
+        match r {
+            Ok(worker) => workers.push(worker),
+            Err(e) => {
+                if first_error.as_ref().is_none_or(|f| offset(&e) < offset(f)) {
+                    first_error = Some(e);
+                }
+            }
+        }
+    }
+    if let Some(e) = first_error {
+        return Err(e);
+    }
+
+    let chunk = workers.iter().map(|w| w.chunking).max().unwrap_or_default();
+    let mut chunk_rejects = Vec::new();
+    let mut threads = Vec::with_capacity(workers.len());
+    let partials = workers
+        .into_iter()
+        .map(|worker| {
+            chunk_rejects.extend(worker.rejects);
+            threads.push(worker.timings);
+            worker.stations
+        })
+        .collect::<Vec<_>>();
+    let stations = merge_tree(partials, opts.checked)?.into_stations(opts)?;
+
+    // Count lines once up to each chunk with rejects, instead of from the start for every one of them
+    chunk_rejects.sort_unstable_by_key(|&(start, _)| start);
+    let mut rejects = Rejects::default();
+    let (mut counted, mut lines) = (0, 0);
+    for (start, mut r) in chunk_rejects {
+        lines += count_lines(&input[counted..start]);
+        counted = start;
+        r.relocate(start, lines);
+        rejects.merge(r);
+    }
+    rejects.lines.truncate(opts.max_rejects);
+
+    let merge = instant.elapsed();
+    log!(
+        opts,
+        "Merging {} threads took {} ms",
+        threads.len(),
+        merge.as_millis()
+    );
+    log!(
+        opts,
+        "====== Processing took {} ms ======",
+        (parse + merge).as_millis()
+    );
+
+    Ok(Stats {
+        stations,
+        decimals: opts.decimals,
+        rejects,
+        timings: Timings {
+            chunk,
+            parse,
+            merge,
+            threads,
+            ..Timings::default()
+        },
+        placement,
+    })
+}
+
+mod fx_hash {
+    // An implementation of the Firefox Hasher
+    // This is kinda a not good hasher but for our use case it's worth a shot!
+
+    use std::hash::{BuildHasher, Hasher};
+
+    pub struct FxHasher {
+        hash: u64,
+    }
+
+    impl Default for FxHasher {
+        #[inline]
+        fn default() -> Self {
+            Self { hash: 0 }
+        }
+    }
+
+    pub(crate) const PI: u64 = 0x0100_0000_01b3;
+
+    impl BuildHasher for FxHasher {
+        type Hasher = Self;
+
+        #[inline]
+        fn build_hasher(&self) -> Self {
+            Self::default()
+        }
+    }
+
+    impl Hasher for FxHasher {
+        #[inline]
+        fn finish(&self) -> u64 {
+            self.hash
+        }
+
+        #[inline]
+        fn write(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.hash = self.hash.wrapping_mul(PI);
+                self.hash ^= *byte as u64;
+            }
+        }
+    }
+}
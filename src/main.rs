@@ -1,233 +1,334 @@
-use std::{arch::asm, collections::HashMap, fs::File, os::fd::AsRawFd};
-
-use fx_hash::FxHasher;
-
-type Row = (i32, i32, i32, usize); // (min, max, sum, count)
-type RowMap<'a> = HashMap<&'a str, Row, FxHasher>; // (min, max, sum, count)
-
-const SEPARATOR: char = ';';
-const FILE_NAME: &str = "measurements.txt";
-const MAP_CAPACITY: usize = 10_000; // Taken from the problem description, "There is a maximum of 10,000 unique station names."
-const PAGE_SIZE: u64 = 4096;
-
-#[inline]
-const fn get_page_round_up(n: u64) -> u64 {
-    (n + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Instant,
+};
+
+use cli::{Args, Command, GenerateArgs};
+use onebrc_rs::{affinity::Placement, generate, ErrorPolicy, Normalization, Options, Rejects};
+use output::{Format, Metadata};
+
+mod bench;
+mod cli;
+mod output;
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1).peekable();
+    // A file called `generate` or `bench` can still be processed as `./generate` or `-- generate`
+    let (command, usage) = match args.peek().map(String::as_str) {
+        Some("generate") => {
+            args.next();
+            (cli::parse_generate(args), cli::GENERATE_USAGE)
+        }
+        Some("bench") => {
+            args.next();
+            (cli::parse_bench(args), cli::BENCH_USAGE)
+        }
+        _ => (cli::parse(args), cli::USAGE),
+    };
+
+    match command {
+        Ok(Command::Run(args)) => run(args),
+        Ok(Command::Generate(args)) => run_generate(args),
+        Ok(Command::Bench(args)) => bench::run(args),
+        Ok(Command::Help) => {
+            print!("{usage}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {e}\n\n{usage}");
+            ExitCode::from(2)
+        }
+    }
 }
 
-fn main() {
+fn run_generate(args: GenerateArgs) -> ExitCode {
     let instant = std::time::Instant::now();
 
-    let num_threads: usize = std::thread::available_parallelism()
-        .expect("Error getting number of threads")
-        .into();
-    let file = File::open(FILE_NAME).expect("File not found");
-    let file_len = file.metadata().expect("Error getting file metadata").len();
-
-    let file_len_rounded = get_page_round_up(file_len);
-
-    let fd = file.as_raw_fd();
-
-    let mut start = 9_usize;
-
-    unsafe {
-        // Map the file to memory, this will allow us to read the file without using the file system
-        asm!(
-            "syscall",
-            inout("rax") start, // syscall number (in), return value (out)
-            in("rdi") 0, // Address of the memory mapping
-            in("rsi") file_len_rounded, // Length of the file, aligned to page size
-            in("rdx") 1, // Read permission
-            in("r10") 2, // Flags
-            in("r8") fd, // File descriptor
-            in("r9") 0, // Offset, aligned to page size
+    // Without a seed every run is different, but the seed is logged so a run can still be repeated
+    let seed = args.seed.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64)
+    });
+    let mut config = generate::Config {
+        rows: args.rows,
+        seed,
+        threads: args.threads,
+        ..args.profile.config()
+    };
+    config.stations = args.stations.unwrap_or(config.stations);
+    config.name_lengths = args.name_lengths.unwrap_or(config.name_lengths);
+    if let Some(s) = args.zipf {
+        config.frequencies = generate::Frequencies::Zipf(s);
+    }
+    config.adversarial |= args.adversarial;
+
+    let stdout = args.output.as_os_str() == "-";
+    let name = if stdout {
+        "<stdout>".into()
+    } else {
+        args.output.display().to_string()
+    };
+
+    if !args.quiet {
+        eprintln!(
+            "Generating {} rows from {} {} stations to {name} with seed {seed}",
+            config.rows,
+            config.stations,
+            args.profile.name()
         );
-        if (start as i64) <= 0 {
-            panic!("Error mapping file ({start})");
-        }
     }
 
-    // We don't need the file handle anymore
-    drop(file);
-
-    let chunk_len = (file_len as usize) / num_threads;
-
-    let mut slices = Vec::with_capacity(num_threads);
-
-    let global_end = start + (file_len as usize) - 1;
-
-    eprintln!(
-        "Total range: 0x{:x} - 0x{:x}",
-        start,
-        start + (file_len as usize) - 1
-    );
+    let result = if stdout {
+        generate::generate(io::stdout().lock(), &config)
+    } else {
+        File::create(&args.output)
+            .and_then(|file| generate::generate(BufWriter::new(file), &config))
+    };
+
+    match result {
+        Ok(()) => {}
+        // Like the results of a run, stdout going away early (`generate -o - | head`) isn't worth complaining about
+        Err(e) if stdout && e.kind() == io::ErrorKind::BrokenPipe => return ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {name}: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
 
-    // Create slices for each thread, finding the next newline for the end
-    for i in 0..num_threads {
-        let end = if i == num_threads - 1 {
-            global_end
-        } else {
-            let mut i = start + chunk_len;
-            while unsafe { *((i) as *mut u8) } != 10 {
-                // Newline
-                i += 1;
-                if i > global_end {
-                    panic!("Error finding newline");
-                }
-            }
-            i
-        };
+    if !args.quiet {
+        eprintln!(
+            "====== Generating took {} ms ======",
+            instant.elapsed().as_millis()
+        );
+    }
 
-        eprintln!("Thread {}: 0x{:x} - 0x{:x}", i + 1, start, end);
+    ExitCode::SUCCESS
+}
 
-        let slice = unsafe {
-            std::str::from_utf8_unchecked(std::slice::from_raw_parts(
-                start as *const u8,
-                end - start,
-            ))
-        };
+fn run(args: Args) -> ExitCode {
+    let start = Instant::now();
+    let normalization = match normalization(&args) {
+        Ok(normalization) => normalization,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let opts = Options {
+        threads: args.threads,
+        separator: args.separator,
+        encoding: args.encoding,
+        normalization,
+        decimals: args.decimals,
+        excess_precision: args.excess_precision,
+        stream: args.stream,
+        chunk_size: args.chunk_size,
+        on_error: args.on_error,
+        max_rejects: args.max_rejects,
+        checked: args.checked,
+        table: args.table,
+        scanner: args.scanner,
+        pin: args.pin,
+        verbose: !args.quiet,
+    };
+
+    let stdin = args.path.as_os_str() == "-";
+    let name = if stdin {
+        "<stdin>".into()
+    } else {
+        args.path.display().to_string()
+    };
+
+    let result = if stdin {
+        onebrc_rs::aggregate_reader(std::io::stdin().lock(), &opts)
+    } else {
+        onebrc_rs::aggregate_file(&args.path, &opts)
+    };
+
+    let stats = match result {
+        Ok(stats) => stats,
+        Err(e) => {
+            eprintln!("error: {name}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
 
-        slices.push(slice);
-        start = end + 1;
+    if args.pin && !args.quiet {
+        report_placement(&stats.placement);
     }
 
-    eprintln!(
-        "====== Init took {} ms ======",
-        instant.elapsed().as_millis()
-    );
-
-    let instant = std::time::Instant::now();
+    if stats.rejects.total() > 0 {
+        let r = &stats.rejects;
+        eprintln!(
+            "warning: {name}: skipped {} bad lines ({} missing separator, {} invalid temperature, {} invalid UTF-8)",
+            r.total(),
+            r.missing_separator,
+            r.bad_temperature,
+            r.invalid_utf8
+        );
+    }
 
-    let map_capacity = MAP_CAPACITY / num_threads;
-
-    let row_map = slices
-        .into_iter()
-        .enumerate()
-        .map(|(t, slice)| {
-            let handle = std::thread::spawn(move || {
-                eprintln!("Thread {} started", t + 1);
-                let mut row_map =
-                    RowMap::with_capacity_and_hasher(map_capacity, FxHasher::default());
-                for line in slice.lines() {
-                    let (name, temp) = line.split_once(SEPARATOR).expect("Error splitting line");
-                    let temp = (temp
-                        .trim()
-                        .parse::<f32>()
-                        .expect("Error parsing temperature")
-                        * 10.0) as i32;
-
-                    row_map
-                        .entry(name)
-                        .and_modify(|entry| {
-                            if temp < entry.0 {
-                                entry.0 = temp;
-                            } else if temp > entry.1 {
-                                entry.1 = temp;
-                            }
-                            entry.2 += temp;
-                            entry.3 += 1;
-                        })
-                        .or_insert_with(|| (temp, temp, temp, 1));
-                }
-                row_map
-            });
-            (t, handle)
-        })
-        .collect::<Vec<_>>(); // Need to collect to wait for threads to finish
-
-    let row_map = row_map
-        .into_iter()
-        .map(|(i, t)| {
-            let r = t.join().expect("Error joining thread");
-            eprintln!("Thread {} finished", i + 1);
-            r
-        })
-        .reduce(|mut a, b| {
-            for (k, v) in b {
-                a.entry(k)
-                    .and_modify(|entry| {
-                        if v.0 < entry.0 {
-                            entry.0 = v.0;
-                        } else if v.1 > entry.1 {
-                            entry.1 = v.1;
-                        }
-                        entry.2 += v.2;
-                        entry.3 += v.3;
-                    })
-                    .or_insert(v);
+    if args.on_error == ErrorPolicy::Report {
+        let path = args.reject_file.clone().unwrap_or_else(|| {
+            let mut path = if stdin {
+                PathBuf::from("stdin").into_os_string()
+            } else {
+                args.path.clone().into_os_string()
+            };
+            path.push(".rejects");
+            PathBuf::from(path)
+        });
+        if let Err(e) = write_rejects(&path, &stats.rejects) {
+            eprintln!("error: couldn't write {}: {e}", path.display());
+            return ExitCode::FAILURE;
+        }
+        if !args.quiet {
+            let written = stats.rejects.lines.len();
+            if (written as u64) < stats.rejects.total() {
+                eprintln!(
+                    "Wrote the first {written} bad lines to {}, see --max-rejects",
+                    path.display()
+                );
+            } else {
+                eprintln!("Wrote bad lines to {}", path.display());
             }
-            a
-        })
-        .expect("Error reducing threads");
+        }
+    }
 
-    eprintln!(
-        "====== Processing took {} ms ======",
-        instant.elapsed().as_millis()
+    let instant = Instant::now();
+
+    let entries = stats.sorted_by(args.collation);
+    let metadata = Metadata {
+        input: (!stdin).then_some(args.path.as_path()),
+        rejected: stats.rejects.total(),
+        timings: &stats.timings,
+        sort: instant.elapsed(),
+        total: start.elapsed(),
+    };
+    let result = output::write_results(
+        io::stdout().lock(),
+        &entries,
+        stats.decimals,
+        &Format {
+            kind: args.format,
+            columns: args.columns.clone(),
+            decimal_separator: args.decimal_separator,
+        },
+        &metadata,
     );
+    match result {
+        Ok(()) => {}
+        // Whoever reads the output stopped early, like `head`, there's nobody left to tell
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: couldn't write results: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
 
-    let instant = std::time::Instant::now();
-
-    let mut entries = row_map.iter().collect::<Vec<_>>();
-    entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
-
-    print!("{{");
-    for (i, (name, (min, max, sum, count))) in entries.iter().enumerate() {
-        print!(
-            "{}{name}={:.1}/{:.1}/{:.1}",
-            if i == 0 { "" } else { ", " },
-            *min as f32 / 10.0,
-            *max as f32 / 10.0,
-            (sum / *count as i32) as f32 / 10.0
+    if !args.quiet {
+        eprintln!(
+            "====== Printing / Sorting took {} ms ======",
+            instant.elapsed().as_millis()
         );
     }
-    println!("}}");
 
-    eprintln!(
-        "====== Printing / Sorting took {} ms ======",
-        instant.elapsed().as_millis()
-    );
+    ExitCode::SUCCESS
 }
 
-mod fx_hash {
-    // An implementation of the Firefox Hasher
-    // This is kinda a not good hasher but for our use case it's worth a shot!
-
-    use std::hash::{BuildHasher, Hasher};
-
-    pub struct FxHasher {
-        hash: u64,
-    }
-
-    impl Default for FxHasher {
-        #[inline]
-        fn default() -> Self {
-            Self { hash: 0 }
+/// How to clean up station names, with the aliases from the command line and alias files
+fn normalization(args: &Args) -> Result<Normalization, String> {
+    let mut normalization = Normalization {
+        trim: args.trim,
+        fold_case: args.fold_case,
+        nfc: args.nfc,
+        ..Normalization::default()
+    };
+    for path in &args.alias_files {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("couldn't read {}: {e}", path.display()))?;
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let (from, to) = cli::parse_alias(line).ok_or_else(|| {
+                format!(
+                    "{}:{}: expected FROM=TO, got {line:?}",
+                    path.display(),
+                    i + 1
+                )
+            })?;
+            normalization.add_alias(&from, &to);
         }
     }
+    // Aliases on the command line win over the files
+    for (from, to) in &args.aliases {
+        normalization.add_alias(from, to);
+    }
+    Ok(normalization)
+}
 
-    const PI: u64 = 0x0100_0000_01b3;
-
-    impl BuildHasher for FxHasher {
-        type Hasher = Self;
-
-        #[inline]
-        fn build_hasher(&self) -> Self {
-            Self::default()
-        }
+/// Print which CPU and NUMA node every worker thread was pinned to
+fn report_placement(placement: &[Placement]) {
+    let mut nodes = placement.iter().map(|p| p.node).collect::<Vec<_>>();
+    nodes.sort_unstable();
+    nodes.dedup();
+    eprintln!(
+        "Pinned {} threads to CPUs on {} NUMA node{}:",
+        placement.len(),
+        nodes.len(),
+        if nodes.len() == 1 { "" } else { "s" }
+    );
+    for node in nodes {
+        let (threads, cpus): (Vec<_>, Vec<_>) = placement
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.node == node)
+            .map(|(t, p)| ((t + 1).to_string(), p.cpu.to_string()))
+            .unzip();
+        eprintln!(
+            "  node {node}: threads {} on CPUs {}",
+            threads.join(", "),
+            cpus.join(", ")
+        );
     }
+}
 
-    impl Hasher for FxHasher {
-        #[inline]
-        fn finish(&self) -> u64 {
-            self.hash
+/// Write the rejected lines to a tab-separated sidecar file, escaped so each one stays on its own row
+fn write_rejects(path: &Path, rejects: &Rejects) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    writeln!(w, "line\toffset\treason\tcontent")?;
+    for e in &rejects.lines {
+        if let Some(line) = e.line_error() {
+            writeln!(
+                w,
+                "{}\t{}\t{}\t{}",
+                line.at.line,
+                line.at.offset,
+                e.reason(),
+                tsv_escaped(&line.content)
+            )?;
         }
+    }
+    w.flush()
+}
 
-        #[inline]
-        fn write(&mut self, bytes: &[u8]) {
-            for byte in bytes {
-                self.hash = self.hash.wrapping_mul(PI);
-                self.hash ^= *byte as u64;
-            }
+/// `s` with backslashes, tabs and line breaks escaped, so it fits in a single TSV field
+fn tsv_escaped(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
         }
     }
+    escaped
 }
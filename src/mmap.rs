@@ -0,0 +1,205 @@
+// Read-only memory mapping of input files
+// We talk to libc directly instead of going through a crate, std already links it for us
+
+use std::{fs::File, io, io::Read, ops::Deref, os::fd::AsRawFd};
+
+use crate::{Error, Result};
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod sys {
+    use std::ffi::{c_int, c_void};
+
+    // These are the same on x86_64 and aarch64 Linux
+    pub const PROT_READ: c_int = 0x1;
+    pub const MAP_PRIVATE: c_int = 0x2;
+    pub const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+    pub const POSIX_FADV_DONTNEED: c_int = 4;
+
+    pub const EACCES: i32 = 13;
+    pub const ENODEV: i32 = 19;
+    pub const EINVAL: i32 = 22;
+
+    extern "C" {
+        pub fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: i64,
+        ) -> *mut c_void;
+        pub fn munmap(addr: *mut c_void, len: usize) -> c_int;
+        pub fn posix_fadvise(fd: c_int, offset: i64, len: i64, advice: c_int) -> c_int;
+    }
+}
+
+/// A read-only, private mapping of a whole file, unmapped on drop
+pub struct Mmap {
+    ptr: *const u8,
+    len: usize,
+}
+
+// The mapping is read-only so sharing it between threads is fine
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    /// Map the entire contents of `file` into memory
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    pub fn map(file: &File) -> io::Result<Self> {
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::OutOfMemory, "file too large to map"))?;
+
+        let ptr = unsafe {
+            sys::mmap(
+                std::ptr::null_mut(),
+                len,
+                sys::PROT_READ,
+                sys::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+
+        if ptr == sys::MAP_FAILED {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(Self {
+                ptr: ptr as *const u8,
+                len,
+            })
+        }
+    }
+
+    #[cfg(not(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    )))]
+    pub fn map(_file: &File) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "memory mapping isn't supported on this platform",
+        ))
+    }
+
+    /// Address the file is mapped at
+    pub fn addr(&self) -> usize {
+        self.ptr as usize
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        #[cfg(all(
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ))]
+        unsafe {
+            sys::munmap(self.ptr as *mut _, self.len);
+        }
+    }
+}
+
+/// Ask the kernel to drop `file` from the page cache, so the next read comes from disk
+///
+/// Only clean pages can be dropped, so anything still waiting to be written back is flushed first.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub fn evict_from_page_cache(file: &File) -> io::Result<()> {
+    file.sync_data()?;
+    // Returns the error number instead of setting errno
+    match unsafe { sys::posix_fadvise(file.as_raw_fd(), 0, 0, sys::POSIX_FADV_DONTNEED) } {
+        0 => Ok(()),
+        e => Err(io::Error::from_raw_os_error(e)),
+    }
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+pub fn evict_from_page_cache(_file: &File) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "evicting files from the page cache isn't supported on this platform",
+    ))
+}
+
+/// Whether a failed mapping means the file just can't be mapped and should be read instead
+fn can_fall_back(err: &io::Error) -> bool {
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    {
+        // ENODEV: pipes and other special files, EINVAL: empty files, EACCES: not opened for reading the way mmap wants
+        matches!(
+            err.raw_os_error(),
+            Some(sys::ENODEV | sys::EINVAL | sys::EACCES)
+        )
+    }
+    #[cfg(not(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    )))]
+    {
+        err.kind() == io::ErrorKind::Unsupported
+    }
+}
+
+/// The contents of an input file, mapped if possible and read into memory otherwise
+pub enum Input {
+    Mapped(Mmap),
+    Buffered(Vec<u8>),
+}
+
+impl Input {
+    /// Map `file`, falling back to reading it if it can't be mapped (e.g. pipes or `/proc` files)
+    pub fn open(file: &mut File) -> Result<Self> {
+        // Zero-length mappings aren't allowed, and there'd be nothing to map anyway
+        if file.metadata()?.len() == 0 {
+            let mut buf = Vec::new();
+            // Files in /proc and the like say they're empty but aren't
+            file.read_to_end(&mut buf)?;
+            return Ok(Self::Buffered(buf));
+        }
+        match Mmap::map(file) {
+            Ok(map) => Ok(Self::Mapped(map)),
+            Err(e) if can_fall_back(&e) => {
+                let mut buf = Vec::new();
+                file.read_to_end(&mut buf)?;
+                Ok(Self::Buffered(buf))
+            }
+            Err(e) => Err(Error::Mmap(e)),
+        }
+    }
+}
+
+impl Deref for Input {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Mapped(map) => map,
+            Self::Buffered(buf) => buf,
+        }
+    }
+}
@@ -0,0 +1,262 @@
+// Cleaning up station names, and putting them in the order people expect
+// Normalization and case folding follow the Unicode standard with tables generated from the Unicode database by
+// scripts/unicode_tables.py. Collation is a locale-independent take on the levels of the Unicode Collation Algorithm:
+// letters first, then accents, then case.
+
+use std::{borrow::Cow, collections::HashMap};
+
+mod tables;
+
+use tables::{CASE_FOLDING, COMBINING_CLASSES, COMPOSITIONS, DECOMPOSITIONS};
+
+/// How station names are cleaned up, so different spellings of one name count as one station
+///
+/// Steps run in the order of the fields. They're applied once to every distinct name after the worker threads are
+/// done, which gives the same stations as normalizing every row would.
+#[derive(Debug, Clone, Default)]
+pub struct Normalization {
+    /// Remove whitespace from both ends
+    pub trim: bool,
+    /// Unicode full case folding, so names that only differ in case are the same
+    pub fold_case: bool,
+    /// Compose names into Normalization Form C, so precomposed and decomposed accents are the same
+    pub nfc: bool,
+    /// Names to replace with another one, keyed by the already normalized name, see [`add_alias`](Self::add_alias)
+    pub aliases: HashMap<String, String>,
+}
+
+impl Normalization {
+    /// Count `from` as `to`, with `from` normalized the same way names are
+    ///
+    /// Set the other fields first.
+    pub fn add_alias(&mut self, from: &str, to: &str) {
+        let from = self.normalize(from).into_owned();
+        self.aliases.insert(from, to.to_string());
+    }
+
+    /// Everything but the aliases
+    fn normalize<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        let mut name = Cow::Borrowed(name);
+        if self.trim && name.trim().len() != name.len() {
+            name = Cow::Owned(name.trim().to_string());
+        }
+        if self.fold_case {
+            name = Cow::Owned(fold_case(&name));
+        }
+        // ASCII is always in NFC
+        if self.nfc && !name.is_ascii() {
+            name = Cow::Owned(nfc(&name));
+        }
+        name
+    }
+
+    /// The name `name` is counted under
+    pub fn apply<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        let name = self.normalize(name);
+        match self.aliases.get(&*name) {
+            Some(alias) => Cow::Owned(alias.clone()),
+            None => name,
+        }
+    }
+}
+
+// Hangul syllables are composed of jamo arithmetically instead of through the tables, see the Unicode standard 3.12
+const S_BASE: u32 = 0xAC00;
+const L_BASE: u32 = 0x1100;
+const V_BASE: u32 = 0x1161;
+const T_BASE: u32 = 0x11A7;
+const L_COUNT: u32 = 19;
+const V_COUNT: u32 = 21;
+const T_COUNT: u32 = 28;
+const N_COUNT: u32 = V_COUNT * T_COUNT;
+const S_COUNT: u32 = L_COUNT * N_COUNT;
+
+fn combining_class(c: char) -> u8 {
+    match COMBINING_CLASSES.binary_search_by(|&(first, _, _)| first.cmp(&c)) {
+        Ok(i) => COMBINING_CLASSES[i].2,
+        Err(0) => 0,
+        Err(i) => {
+            let (_, last, class) = COMBINING_CLASSES[i - 1];
+            if c <= last {
+                class
+            } else {
+                0
+            }
+        }
+    }
+}
+
+/// Push the full canonical decomposition of `c`
+fn decompose(c: char, out: &mut Vec<char>) {
+    let s = (c as u32).wrapping_sub(S_BASE);
+    if s < S_COUNT {
+        let (l, v, t) = (
+            L_BASE + s / N_COUNT,
+            V_BASE + s % N_COUNT / T_COUNT,
+            s % T_COUNT,
+        );
+        out.extend([l, v].map(|j| char::from_u32(j).unwrap()));
+        if t != 0 {
+            out.push(char::from_u32(T_BASE + t).unwrap());
+        }
+        return;
+    }
+    match DECOMPOSITIONS.binary_search_by(|&(from, _)| from.cmp(&c)) {
+        Ok(i) => out.extend(DECOMPOSITIONS[i].1.chars()),
+        Err(_) => out.push(c),
+    }
+}
+
+/// The character `first` and `second` compose into, if any
+fn compose(first: char, second: char) -> Option<char> {
+    let (f, s) = (first as u32, second as u32);
+    if (L_BASE..L_BASE + L_COUNT).contains(&f) && (V_BASE..V_BASE + V_COUNT).contains(&s) {
+        return char::from_u32(S_BASE + ((f - L_BASE) * V_COUNT + s - V_BASE) * T_COUNT);
+    }
+    if f.wrapping_sub(S_BASE) < S_COUNT
+        && (f - S_BASE).is_multiple_of(T_COUNT)
+        && (T_BASE + 1..T_BASE + T_COUNT).contains(&s)
+    {
+        return char::from_u32(f + s - T_BASE);
+    }
+    COMPOSITIONS
+        .binary_search_by(|&(a, b, _)| (a, b).cmp(&(first, second)))
+        .ok()
+        .map(|i| COMPOSITIONS[i].2)
+}
+
+/// Canonical decomposition of `s`, in canonical order
+fn nfd_chars(s: &str) -> Vec<char> {
+    let mut chars = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        decompose(c, &mut chars);
+    }
+    // Put every run of combining marks in order of their class, marks of the same class keep their order
+    let mut start = 0;
+    while start < chars.len() {
+        if combining_class(chars[start]) == 0 {
+            start += 1;
+            continue;
+        }
+        let end = chars[start..]
+            .iter()
+            .position(|&c| combining_class(c) == 0)
+            .map_or(chars.len(), |i| start + i);
+        chars[start..end].sort_by_key(|&c| combining_class(c));
+        start = end;
+    }
+    chars
+}
+
+/// Normalization Form D: everything decomposed
+pub fn nfd(s: &str) -> String {
+    nfd_chars(s).into_iter().collect()
+}
+
+/// Normalization Form C: decomposed, then composed again wherever possible
+pub fn nfc(s: &str) -> String {
+    let mut out: Vec<char> = Vec::with_capacity(s.len());
+    // Index of the last character with class 0 that others could still compose with
+    let mut starter = None;
+    // Class of the last character since the starter
+    let mut last_class = 0;
+    for c in nfd_chars(s) {
+        let class = combining_class(c);
+        if let Some(i) = starter {
+            // Anything between `c` and the starter with the same or a higher class blocks them from composing
+            let blocked = out.len() > i + 1 && (last_class == 0 || last_class >= class);
+            if !blocked {
+                if let Some(composed) = compose(out[i], c) {
+                    out[i] = composed;
+                    continue;
+                }
+            }
+        }
+        if class == 0 {
+            starter = Some(out.len());
+        }
+        last_class = class;
+        out.push(c);
+    }
+    out.into_iter().collect()
+}
+
+/// Unicode full case folding
+pub fn fold_case(s: &str) -> String {
+    if s.is_ascii() {
+        return s.to_ascii_lowercase();
+    }
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match CASE_FOLDING.binary_search_by(|&(from, _)| from.cmp(&c)) {
+            Ok(i) => out.push_str(CASE_FOLDING[i].1),
+            Err(_) => out.push(c),
+        }
+    }
+    out
+}
+
+/// Order stations are listed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collation {
+    /// By code point, which is the same as by UTF-8 bytes
+    Codepoint,
+    /// Letters ignoring accents and case first, then accents, then lowercase before uppercase, like a dictionary
+    Unicode,
+}
+
+impl Collation {
+    pub const ALL: [Self; 2] = [Self::Codepoint, Self::Unicode];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Codepoint => "codepoint",
+            Self::Unicode => "unicode",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+}
+
+/// What [`Collation::Unicode`] compares names by, one level after the other
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CollationKey {
+    /// The letters, without accents and case folded
+    primary: Vec<char>,
+    /// A zero for every letter, followed by the accents on it
+    secondary: Vec<u32>,
+    /// Whether case folding changed each letter, so lowercase comes first
+    tertiary: Vec<bool>,
+}
+
+impl CollationKey {
+    pub fn new(name: &str) -> Self {
+        let mut key = Self {
+            primary: Vec::with_capacity(name.len()),
+            secondary: Vec::with_capacity(name.len()),
+            tertiary: Vec::with_capacity(name.len()),
+        };
+        let mut buf = [0; 4];
+        for c in nfd_chars(name) {
+            if combining_class(c) != 0 {
+                key.secondary.push(c as u32);
+                continue;
+            }
+            let c = &*c.encode_utf8(&mut buf);
+            let fold = fold_case(c);
+            // Folding can add letters (ß is ss) and accents, all of them count as this letter's
+            for f in nfd_chars(&fold) {
+                if combining_class(f) != 0 {
+                    key.secondary.push(f as u32);
+                } else {
+                    key.primary.push(f);
+                    key.secondary.push(0);
+                    key.tertiary.push(fold != c);
+                }
+            }
+        }
+        key
+    }
+}
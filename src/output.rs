@@ -0,0 +1,320 @@
+// Writing the results out in each of the output formats
+// Everything is rendered into one buffer and written to stdout in a single go. Temperatures are fixed point, so
+// they're printed straight from the integers, which is quicker than going through floats and keeps sums exact.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+    time::Duration,
+};
+
+use onebrc_rs::{StationStats, Sum, Timings};
+
+use crate::cli::{Column, OutputFormat};
+
+/// Bytes to reserve per station on top of its name, more than any format needs with the default columns
+const ROOM_PER_STATION: usize = 128;
+
+/// How to write the results
+pub struct Format {
+    pub kind: OutputFormat,
+    /// For CSV and TSV
+    pub columns: Vec<Column>,
+    /// For CSV and TSV
+    pub decimal_separator: char,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self {
+            kind: OutputFormat::Brace,
+            columns: Column::DEFAULT.to_vec(),
+            decimal_separator: '.',
+        }
+    }
+}
+
+/// What the machine-readable formats report besides the stations
+pub struct Metadata<'a> {
+    /// `None` for stdin
+    pub input: Option<&'a Path>,
+    pub rejected: u64,
+    pub timings: &'a Timings,
+    pub sort: Duration,
+    /// Since the start, up to writing the results
+    pub total: Duration,
+}
+
+/// Write the results for `entries` to `out` with a single write
+///
+/// Give it a locked stdout, not a `BufWriter` around one: the output is already buffered here.
+pub fn write_results(
+    mut out: impl Write,
+    entries: &[(&str, &StationStats)],
+    decimals: u8,
+    format: &Format,
+    metadata: &Metadata,
+) -> io::Result<()> {
+    let names = entries.iter().map(|(name, _)| name.len()).sum::<usize>();
+    let mut buf = Vec::with_capacity(names + entries.len() * ROOM_PER_STATION + 1024);
+    render(&mut buf, entries, decimals, format, metadata)?;
+    out.write_all(&buf)?;
+    out.flush()
+}
+
+fn render(
+    buf: &mut Vec<u8>,
+    entries: &[(&str, &StationStats)],
+    decimals: u8,
+    format: &Format,
+    metadata: &Metadata,
+) -> io::Result<()> {
+    let (open, delimiter, close) = match format.kind {
+        OutputFormat::Brace => ("{", ", ", "}\n"),
+        OutputFormat::Lines => ("", "\n", "\n"),
+        OutputFormat::Json => return render_json(buf, entries, decimals, metadata),
+        // RFC 4180 wants CRLF, TSV is usually read line by line
+        OutputFormat::Csv => {
+            render_delimited(buf, entries, decimals, format, ',', "\r\n");
+            return Ok(());
+        }
+        OutputFormat::Tsv => {
+            render_delimited(buf, entries, decimals, format, '\t', "\n");
+            return Ok(());
+        }
+    };
+
+    buf.extend_from_slice(open.as_bytes());
+    for (i, (name, station)) in entries.iter().enumerate() {
+        if i > 0 {
+            buf.extend_from_slice(delimiter.as_bytes());
+        }
+        buf.extend_from_slice(name.as_bytes());
+        buf.push(b'=');
+        push_fixed(buf, station.min as Sum, decimals, '.');
+        buf.push(b'/');
+        push_fixed(buf, station.mean() as Sum, decimals, '.');
+        buf.push(b'/');
+        push_fixed(buf, station.max as Sum, decimals, '.');
+    }
+    buf.extend_from_slice(close.as_bytes());
+    Ok(())
+}
+
+/// One object with the metadata and an array of stations, so the order survives parsers that don't keep key order
+fn render_json(
+    buf: &mut Vec<u8>,
+    entries: &[(&str, &StationStats)],
+    decimals: u8,
+    metadata: &Metadata,
+) -> io::Result<()> {
+    let rows = entries.iter().map(|(_, s)| s.count).sum::<u64>();
+    let t = metadata.timings;
+    buf.extend_from_slice(b"{\"metadata\":{\"input\":");
+    match metadata.input {
+        Some(path) => push_json_string(buf, &path.to_string_lossy()),
+        None => buf.extend_from_slice(b"null"),
+    }
+    write!(
+        buf,
+        ",\"rows\":{},\"rejected\":{},\"stations\":{},\"decimals\":{decimals},\"elapsed_ms\":{{",
+        rows + metadata.rejected,
+        metadata.rejected,
+        entries.len(),
+    )?;
+    let phases = [
+        ("map", t.map),
+        ("chunk", t.chunk),
+        ("parse", t.parse),
+        ("merge", t.merge),
+        ("sort", metadata.sort),
+        ("total", metadata.total),
+    ];
+    for (i, (phase, elapsed)) in phases.into_iter().enumerate() {
+        let comma = if i == 0 { "" } else { "," };
+        write!(
+            buf,
+            "{comma}\"{phase}\":{:.3}",
+            elapsed.as_secs_f64() * 1000.0
+        )?;
+    }
+    buf.extend_from_slice(b"}},\"stations\":[");
+    for (i, (name, s)) in entries.iter().enumerate() {
+        if i > 0 {
+            buf.push(b',');
+        }
+        buf.extend_from_slice(b"{\"name\":");
+        push_json_string(buf, name);
+        buf.extend_from_slice(b",\"min\":");
+        push_fixed(buf, s.min as Sum, decimals, '.');
+        buf.extend_from_slice(b",\"max\":");
+        push_fixed(buf, s.max as Sum, decimals, '.');
+        buf.extend_from_slice(b",\"mean\":");
+        push_fixed(buf, s.mean() as Sum, decimals, '.');
+        buf.extend_from_slice(b",\"sum\":");
+        push_fixed(buf, s.sum(), decimals, '.');
+        buf.extend_from_slice(b",\"count\":");
+        push_unsigned(buf, s.count, 0, '.', 1);
+        buf.push(b'}');
+    }
+    buf.extend_from_slice(b"]}\n");
+    Ok(())
+}
+
+/// A header row and a row per station, with fields quoted wherever they'd otherwise break the row up
+fn render_delimited(
+    buf: &mut Vec<u8>,
+    entries: &[(&str, &StationStats)],
+    decimals: u8,
+    format: &Format,
+    delimiter: char,
+    newline: &str,
+) {
+    let mut sep = [0; 4];
+    let sep = delimiter.encode_utf8(&mut sep).as_bytes();
+    for (i, column) in format.columns.iter().enumerate() {
+        if i > 0 {
+            buf.extend_from_slice(sep);
+        }
+        buf.extend_from_slice(column.name().as_bytes());
+    }
+    buf.extend_from_slice(newline.as_bytes());
+
+    let point = format.decimal_separator;
+    // Numbers only need quoting if the point is the delimiter, so they're rendered here first in that case
+    let quote_numbers = point == delimiter;
+    let mut field = Vec::new();
+    for (name, s) in entries {
+        for (i, column) in format.columns.iter().enumerate() {
+            if i > 0 {
+                buf.extend_from_slice(sep);
+            }
+            let value = match column {
+                Column::Name => {
+                    push_csv_field(buf, name, delimiter);
+                    continue;
+                }
+                Column::Count => {
+                    push_unsigned(buf, s.count, 0, point, 1);
+                    continue;
+                }
+                Column::Min => s.min as Sum,
+                Column::Mean => s.mean() as Sum,
+                Column::Max => s.max as Sum,
+                Column::Sum => s.sum(),
+                #[cfg(feature = "stddev")]
+                Column::Stddev => s.stddev() as Sum,
+                #[cfg(not(feature = "stddev"))]
+                Column::Stddev => unreachable!("only accepted with the stddev feature"),
+            };
+            if quote_numbers && decimals > 0 {
+                field.clear();
+                push_fixed(&mut field, value, decimals, point);
+                buf.push(b'"');
+                buf.extend_from_slice(&field);
+                buf.push(b'"');
+            } else {
+                push_fixed(buf, value, decimals, point);
+            }
+        }
+        buf.extend_from_slice(newline.as_bytes());
+    }
+}
+
+/// `field` in double quotes if it contains `delimiter`, a quote or a line break, with quotes doubled (RFC 4180 2.6, 2.7)
+fn push_csv_field(buf: &mut Vec<u8>, field: &str, delimiter: char) {
+    if !field.contains([delimiter, '"', '\r', '\n']) {
+        buf.extend_from_slice(field.as_bytes());
+        return;
+    }
+    buf.push(b'"');
+    for part in field.split_inclusive('"') {
+        buf.extend_from_slice(part.as_bytes());
+        if part.ends_with('"') {
+            buf.push(b'"');
+        }
+    }
+    buf.push(b'"');
+}
+
+/// Append the fixed-point `value`, with `decimals` of its digits after `point`
+fn push_fixed(buf: &mut Vec<u8>, value: Sum, decimals: u8, point: char) {
+    // Split into u64s of 18 digits each, so only a sum past 10^18 needs a 128-bit division
+    let chunk = (1_000_000_000_000_000_000 as Sum).unsigned_abs();
+    if value < 0 {
+        buf.push(b'-');
+    }
+    let mut n = value.unsigned_abs();
+    let mut chunks = [0; 3];
+    let mut len = 0;
+    loop {
+        chunks[len] = (n % chunk) as u64;
+        n /= chunk;
+        len += 1;
+        if n == 0 {
+            break;
+        }
+    }
+    for i in (0..len).rev() {
+        let (decimals, width) = match (i, i + 1 == len) {
+            (0, true) => (decimals, 1),
+            (0, false) => (decimals, 18),
+            (_, true) => (0, 1),
+            (_, false) => (0, 18),
+        };
+        push_unsigned(buf, chunks[i], decimals, point, width);
+    }
+}
+
+/// Append `n` with at least `width` digits and `decimals` of them after `point`
+#[inline]
+fn push_unsigned(buf: &mut Vec<u8>, mut n: u64, decimals: u8, point: char, width: usize) {
+    // u64::MAX has 20 digits
+    let mut digits = [0; 20];
+    let mut start = digits.len();
+    // At least one digit before the point
+    let width = width.max(decimals as usize + 1);
+    while n > 0 || digits.len() - start < width {
+        start -= 1;
+        digits[start] = b'0' + (n % 10) as u8;
+        n /= 10;
+    }
+    let (whole, frac) = digits[start..].split_at(digits.len() - start - decimals as usize);
+    buf.extend_from_slice(whole);
+    if decimals > 0 {
+        let mut p = [0; 4];
+        buf.extend_from_slice(point.encode_utf8(&mut p).as_bytes());
+        buf.extend_from_slice(frac);
+    }
+}
+
+/// Append `s` quoted and escaped as a JSON string
+fn push_json_string(buf: &mut Vec<u8>, s: &str) {
+    buf.push(b'"');
+    for c in s.chars() {
+        match c {
+            '"' => buf.extend_from_slice(b"\\\""),
+            '\\' => buf.extend_from_slice(b"\\\\"),
+            '\n' => buf.extend_from_slice(b"\\n"),
+            '\r' => buf.extend_from_slice(b"\\r"),
+            '\t' => buf.extend_from_slice(b"\\t"),
+            c if (c as u32) < 0x20 => {
+                let hex = b"0123456789abcdef";
+                buf.extend_from_slice(b"\\u00");
+                buf.extend_from_slice(&[hex[c as usize >> 4], hex[c as usize & 15]]);
+            }
+            c => {
+                let mut utf8 = [0; 4];
+                buf.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            }
+        }
+    }
+    buf.push(b'"');
+}
+
+/// Quote and escape `s` as a JSON string
+pub fn json_string(s: &str) -> String {
+    let mut buf = Vec::with_capacity(s.len() + 2);
+    push_json_string(&mut buf, s);
+    String::from_utf8(buf).expect("escaping keeps UTF-8 intact")
+}
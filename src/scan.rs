@@ -0,0 +1,287 @@
+// Finding separators and newlines 64 bytes at a time
+// Every backend turns a block into two bitmasks, one bit per byte, and the row loop walks the set bits instead of
+// looking at every byte. The SIMD ones are picked at runtime, SWAR works everywhere.
+
+use crate::table::NameHasher;
+
+/// How the input is searched for separators and newlines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scanner {
+    /// The fastest one this CPU supports
+    Auto,
+    /// 32 bytes at a time, x86_64 with AVX2 only
+    Avx2,
+    /// 16 bytes at a time, any x86_64
+    Sse2,
+    /// 16 bytes at a time, aarch64 only
+    Neon,
+    /// 8 bytes at a time in plain integer registers, anywhere
+    Swar,
+}
+
+impl Scanner {
+    pub const ALL: [Self; 5] = [Self::Auto, Self::Avx2, Self::Sse2, Self::Neon, Self::Swar];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Avx2 => "avx2",
+            Self::Sse2 => "sse2",
+            Self::Neon => "neon",
+            Self::Swar => "swar",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.name() == name)
+    }
+
+    /// Whether this CPU can run it
+    pub fn is_supported(self) -> bool {
+        match self {
+            Self::Auto | Self::Swar => true,
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 => std::arch::is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "x86_64")]
+            Self::Sse2 => std::arch::is_x86_feature_detected!("sse2"),
+            #[cfg(target_arch = "aarch64")]
+            Self::Neon => std::arch::is_aarch64_feature_detected!("neon"),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    /// The scanner that actually runs: the fastest supported one for `Auto`, SWAR if this one isn't supported
+    pub fn resolve(self) -> Self {
+        match self {
+            Self::Auto => [Self::Avx2, Self::Sse2, Self::Neon]
+                .into_iter()
+                .find(|s| s.is_supported())
+                .unwrap_or(Self::Swar),
+            s if s.is_supported() => s,
+            _ => Self::Swar,
+        }
+    }
+}
+
+const BLOCK: usize = 64;
+
+/// Whether `separator`, as it's encoded in the input, can be searched for byte by byte
+///
+/// It has to be a single byte, and not NUL which would match the padding after the end of the input.
+pub(crate) fn can_scan_for(separator: &[u8]) -> bool {
+    matches!(separator, [b] if *b != 0)
+}
+
+/// One way of finding bytes in a block
+pub(crate) trait Classify {
+    /// Bitmasks of the bytes in `block` equal to `separator` and to `\n`, bit `i` for byte `i`
+    fn classify(block: &[u8; BLOCK], separator: u8) -> (u64, u64);
+}
+
+pub(crate) struct Swar;
+
+impl Classify for Swar {
+    #[inline]
+    fn classify(block: &[u8; BLOCK], separator: u8) -> (u64, u64) {
+        const LOW_BITS: u64 = 0x7F7F_7F7F_7F7F_7F7F;
+        const LANES: u64 = 0x0101_0101_0101_0101;
+
+        // High bit of every zero byte in `x`, without the false positives of the usual `(x - 0x01..) & !x` trick
+        let zero_bytes = |x: u64| !(((x & LOW_BITS) + LOW_BITS) | x | LOW_BITS);
+        // Gather the high bits of the 8 bytes into the top byte, byte `i` ending up in bit `56 + i`
+        let gather = |high_bits: u64| (high_bits >> 7).wrapping_mul(0x0102_0408_1020_4080) >> 56;
+
+        let (mut seps, mut newlines) = (0, 0);
+        for (i, word) in block.chunks_exact(8).enumerate() {
+            let word = u64::from_le_bytes(word.try_into().unwrap());
+            seps |= gather(zero_bytes(word ^ (LANES * separator as u64))) << (8 * i);
+            newlines |= gather(zero_bytes(word ^ (LANES * b'\n' as u64))) << (8 * i);
+        }
+        (seps, newlines)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(crate) struct Avx2;
+
+#[cfg(target_arch = "x86_64")]
+impl Classify for Avx2 {
+    #[inline]
+    fn classify(block: &[u8; BLOCK], separator: u8) -> (u64, u64) {
+        #[target_feature(enable = "avx2")]
+        unsafe fn classify(block: &[u8; BLOCK], separator: u8) -> (u64, u64) {
+            use std::arch::x86_64::*;
+
+            let (sep, newline) = (
+                _mm256_set1_epi8(separator as i8),
+                _mm256_set1_epi8(b'\n' as i8),
+            );
+            let (mut seps, mut newlines) = (0, 0);
+            for i in 0..BLOCK / 32 {
+                let v = _mm256_loadu_si256(block.as_ptr().add(32 * i).cast());
+                let s = _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, sep)) as u32;
+                let n = _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, newline)) as u32;
+                seps |= (s as u64) << (32 * i);
+                newlines |= (n as u64) << (32 * i);
+            }
+            (seps, newlines)
+        }
+
+        // SAFETY: Only used after `Scanner::resolve` checked the CPU has AVX2
+        unsafe { classify(block, separator) }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(crate) struct Sse2;
+
+#[cfg(target_arch = "x86_64")]
+impl Classify for Sse2 {
+    #[inline]
+    fn classify(block: &[u8; BLOCK], separator: u8) -> (u64, u64) {
+        use std::arch::x86_64::*;
+
+        // SAFETY: SSE2 is part of the x86_64 baseline, and the loads are unaligned ones within `block`
+        unsafe {
+            let (sep, newline) = (_mm_set1_epi8(separator as i8), _mm_set1_epi8(b'\n' as i8));
+            let (mut seps, mut newlines) = (0, 0);
+            for i in 0..BLOCK / 16 {
+                let v = _mm_loadu_si128(block.as_ptr().add(16 * i).cast());
+                let s = _mm_movemask_epi8(_mm_cmpeq_epi8(v, sep)) as u32;
+                let n = _mm_movemask_epi8(_mm_cmpeq_epi8(v, newline)) as u32;
+                seps |= (s as u64) << (16 * i);
+                newlines |= (n as u64) << (16 * i);
+            }
+            (seps, newlines)
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+pub(crate) struct Neon;
+
+#[cfg(target_arch = "aarch64")]
+impl Classify for Neon {
+    #[inline]
+    fn classify(block: &[u8; BLOCK], separator: u8) -> (u64, u64) {
+        use std::arch::aarch64::*;
+
+        // SAFETY: NEON is part of the aarch64 baseline, and the loads are within `block`
+        unsafe {
+            let p = block.as_ptr();
+            let v = [
+                vld1q_u8(p),
+                vld1q_u8(p.add(16)),
+                vld1q_u8(p.add(32)),
+                vld1q_u8(p.add(48)),
+            ];
+            // NEON has no movemask, so weight every lane by its bit and add neighbouring lanes together until
+            // the 64 lanes are 8 bytes of 8 bits each
+            const WEIGHTS: [u8; 16] = [1, 2, 4, 8, 16, 32, 64, 128, 1, 2, 4, 8, 16, 32, 64, 128];
+            let weights = vld1q_u8(WEIGHTS.as_ptr());
+            let mask = |needle: uint8x16_t| {
+                let t = v.map(|v| vandq_u8(vceqq_u8(v, needle), weights));
+                let sum = vpaddq_u8(vpaddq_u8(t[0], t[1]), vpaddq_u8(t[2], t[3]));
+                vgetq_lane_u64::<0>(vreinterpretq_u64_u8(vpaddq_u8(sum, sum)))
+            };
+            (mask(vdupq_n_u8(separator)), mask(vdupq_n_u8(b'\n')))
+        }
+    }
+}
+
+/// Walks the separators and newlines of some input, in order
+pub(crate) struct Delimiters<'a, C> {
+    bytes: &'a [u8],
+    separator: u8,
+    /// Offset of the current block
+    block: usize,
+    seps: u64,
+    newlines: u64,
+    classify: std::marker::PhantomData<C>,
+}
+
+impl<'a, C: Classify> Delimiters<'a, C> {
+    pub(crate) fn new(bytes: &'a [u8], separator: u8) -> Self {
+        let mut delimiters = Self {
+            bytes,
+            separator,
+            block: 0,
+            seps: 0,
+            newlines: 0,
+            classify: std::marker::PhantomData,
+        };
+        delimiters.load();
+        delimiters
+    }
+
+    /// Classify the block at `self.block`, padding it with zeros past the end of the input
+    #[inline]
+    fn load(&mut self) {
+        let (seps, newlines) = match self.bytes.get(self.block..self.block + BLOCK) {
+            Some(block) => C::classify(block.try_into().unwrap(), self.separator),
+            None if self.block < self.bytes.len() => {
+                let mut block = [0; BLOCK];
+                let rest = &self.bytes[self.block..];
+                block[..rest.len()].copy_from_slice(rest);
+                C::classify(&block, self.separator)
+            }
+            None => (0, 0),
+        };
+        (self.seps, self.newlines) = (seps, newlines);
+    }
+
+    /// Move on to the next block, `false` at the end of the input
+    #[inline]
+    fn advance(&mut self) -> bool {
+        self.block += BLOCK;
+        if self.block >= self.bytes.len() {
+            return false;
+        }
+        self.load();
+        true
+    }
+
+    /// Position of the next separator or newline and whether it's a newline, along with the
+    /// [`hash_name`](crate::table::hash_name) of the name that starts at `start` and ends there
+    ///
+    /// The name is hashed while it's being scanned, so its words are mixed in as the blocks holding them go by
+    /// instead of in a second pass once the separator turns up. Without a delimiter the hash means nothing.
+    #[inline(always)]
+    pub(crate) fn next_delimiter_hashed(&mut self, start: usize) -> (Option<(usize, bool)>, u64) {
+        let mut hasher = NameHasher::new(start);
+        loop {
+            let any = self.seps | self.newlines;
+            if any != 0 {
+                let bit = any.trailing_zeros();
+                let newline = self.newlines >> bit & 1 == 1;
+                self.seps &= !(1 << bit);
+                self.newlines &= !(1 << bit);
+                let at = self.block + bit as usize;
+                return (Some((at, newline)), hasher.finish(self.bytes, at));
+            }
+            // No delimiter in the rest of this block, so the name goes on at least to its end
+            hasher.update(self.bytes, (self.block + BLOCK).min(self.bytes.len()));
+            if !self.advance() {
+                return (None, 0);
+            }
+        }
+    }
+
+    /// Position of the next newline, skipping any separators before it
+    #[inline(always)]
+    pub(crate) fn next_newline(&mut self) -> Option<usize> {
+        loop {
+            if self.newlines != 0 {
+                let bit = self.newlines.trailing_zeros();
+                self.newlines &= self.newlines - 1;
+                // Drop the separators up to the newline, they're part of this line
+                self.seps &= !(u64::MAX >> (63 - bit));
+                return Some(self.block + bit as usize);
+            }
+            if !self.advance() {
+                return None;
+            }
+        }
+    }
+}
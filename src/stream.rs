@@ -0,0 +1,352 @@
+// Streaming input, for stdin, pipes and anything else we can't (or don't want to) map into memory
+// One thread reads fixed-size blocks that always end on a newline and hands them to the workers over a bounded channel,
+// the workers hand the buffers back once they're done with them
+
+use std::{
+    io::{self, Read},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use crate::{
+    accumulate, for_each_row, merge_tree, start_worker, table::Added, Error, Options,
+    PartialStations, Rejects, Result, StationStats, Stats, ThreadTimings, Timings, MAP_CAPACITY,
+};
+
+type OwnedStations = PartialStations<Box<[u8]>>;
+
+/// A run of full lines from the input
+struct Block {
+    /// The whole buffer, only the first `len` bytes of which are the block
+    buf: Vec<u8>,
+    len: usize,
+    /// Byte offset of the block in the input
+    offset: usize,
+}
+
+impl Block {
+    fn data(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+const BLOCK_SIZE: usize = 4 * 1024 * 1024;
+const BLOCKS_PER_THREAD: usize = 2; // How many blocks can be waiting in the channel per worker
+
+/// Read `reader` until the end and send every full-line block to the workers, reusing the buffers they send back
+///
+/// Stops early once `stop` is set, by a worker that failed on a bad line.
+fn read_blocks(
+    mut reader: impl Read,
+    tx: mpsc::SyncSender<Block>,
+    recycled: mpsc::Receiver<Vec<u8>>,
+    stop: &AtomicBool,
+) -> io::Result<usize> {
+    let mut buf = vec![0; BLOCK_SIZE];
+    let mut filled = 0;
+    let mut blocks = 0;
+    let mut offset = 0;
+
+    loop {
+        let n = match reader.read(&mut buf[filled..]) {
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        filled += n;
+
+        let eof = n == 0;
+
+        if !eof && filled < buf.len() {
+            continue;
+        }
+
+        let block_end = if eof {
+            filled
+        } else {
+            match buf[..filled].iter().rposition(|&b| b == b'\n') {
+                Some(i) => i + 1,
+                None => {
+                    // A single line longer than the whole buffer, make room and keep reading
+                    buf.resize(buf.len() * 2, 0);
+                    continue;
+                }
+            }
+        };
+
+        if block_end > 0 {
+            // Carry the partial trailing line over into the next buffer, a returned one if there is one. They're
+            // never shortened, so only a buffer that grew for a long line has to be zeroed again.
+            let mut next = recycled.try_recv().unwrap_or_default();
+            if next.len() < buf.len() {
+                next.resize(buf.len(), 0);
+            }
+            next[..filled - block_end].copy_from_slice(&buf[block_end..filled]);
+            filled -= block_end;
+
+            let block = Block {
+                buf: std::mem::replace(&mut buf, next),
+                len: block_end,
+                offset,
+            };
+            offset += block_end;
+
+            if stop.load(Ordering::Relaxed) || tx.send(block).is_err() {
+                // Every worker is gone, no point reading any further
+                break;
+            }
+            blocks += 1;
+        }
+
+        if eof {
+            break;
+        }
+    }
+
+    Ok(blocks)
+}
+
+/// Aggregate a block into `stations`
+///
+/// Returns the number of lines in the block, bad ones included.
+fn aggregate_block(
+    data: &[u8],
+    opts: &Options,
+    stations: &mut OwnedStations,
+    rejects: &mut Rejects,
+) -> Result<u64> {
+    let mut overflowed = None;
+    let rows = match stations {
+        PartialStations::Std(row_map) => for_each_row(data, opts, rejects, |name, _, temp| {
+            match row_map.get_mut(name) {
+                Some(entry) => {
+                    if !accumulate(entry, temp, opts.checked) {
+                        overflowed.get_or_insert(name);
+                    }
+                }
+                None if !opts.encoding.accepts(name) => return false,
+                None => {
+                    row_map.insert(name.into(), StationStats::new(temp));
+                }
+            }
+            true
+        })?,
+        PartialStations::Open(table) => for_each_row(data, opts, rejects, |name, hash, temp| {
+            match table.add(name, hash, temp, opts.checked, |name| {
+                opts.encoding.accepts(name)
+            }) {
+                Added::Counted => true,
+                Added::Overflowed => {
+                    overflowed.get_or_insert(name);
+                    true
+                }
+                Added::Refused => false,
+            }
+        })?,
+    };
+    if let Some(name) = overflowed {
+        return Err(Error::Overflow(opts.encoding.decode(name).into_owned()));
+    }
+    Ok(rows)
+}
+
+/// What one worker made of the blocks it got
+struct Worker {
+    stations: OwnedStations,
+    /// Offset and number of lines of every block it aggregated, for working out line numbers at the end
+    lines: Vec<(usize, u64)>,
+    /// Rejects of every block that had some, with line numbers relative to the block starting at that offset
+    rejects: Vec<(usize, Rejects)>,
+    /// The bad line it stopped at, relative to the block starting at that offset
+    error: Option<(usize, Error)>,
+    timings: ThreadTimings,
+}
+
+/// Aggregate blocks until there are no more or one of them fails, sending every buffer back to the reader
+///
+/// A failed block sets `stop`, which has every worker stop taking blocks. The ones still waiting in the channel
+/// all come after it, so the first bad line is still found and every line before it counted.
+fn aggregate_blocks(
+    rx: &Mutex<mpsc::Receiver<Block>>,
+    recycle: mpsc::Sender<Vec<u8>>,
+    stop: &AtomicBool,
+    opts: &Options,
+    map_capacity: usize,
+) -> Worker {
+    let mut worker = Worker {
+        stations: OwnedStations::new(opts, map_capacity),
+        lines: Vec::new(),
+        rejects: Vec::new(),
+        error: None,
+        timings: ThreadTimings::default(),
+    };
+    // Blocks come in order, so keeping this thread's first few rejects keeps all of the first few overall
+    let mut kept_budget = opts.max_rejects;
+
+    while !stop.load(Ordering::Relaxed) {
+        // Only hold the lock while waiting, not while processing
+        let block = rx.lock().expect("Error locking channel").recv();
+        let Ok(block) = block else {
+            break;
+        };
+
+        // Only count the time spent working, not waiting for the reader
+        let instant = Instant::now();
+        let mut rejects = Rejects::default();
+        match aggregate_block(block.data(), opts, &mut worker.stations, &mut rejects) {
+            Ok(rows) => {
+                worker.lines.push((block.offset, rows));
+                if rejects.total() > 0 {
+                    rejects.keep_within(&mut kept_budget);
+                    worker.rejects.push((block.offset, rejects));
+                }
+                worker.timings.rows += rows;
+                worker.timings.bytes += block.len as u64;
+            }
+            Err(e) => {
+                stop.store(true, Ordering::Relaxed);
+                worker.error = Some((block.offset, e));
+            }
+        }
+        worker.timings.elapsed += instant.elapsed();
+
+        // The reader might be done already
+        let _ = recycle.send(block.buf);
+    }
+
+    worker
+}
+
+/// Aggregate measurements from any reader, without needing the whole input in memory at once
+pub fn aggregate_reader(reader: impl Read, opts: &Options) -> Result<Stats> {
+    let instant = Instant::now();
+
+    let num_threads = opts.num_threads();
+    let map_capacity = MAP_CAPACITY / num_threads;
+    let placement = opts.placement(num_threads)?;
+
+    log!(
+        opts,
+        "Streaming input in {} KiB blocks to {num_threads} threads",
+        BLOCK_SIZE / 1024
+    );
+
+    let (tx, rx) = mpsc::sync_channel(num_threads * BLOCKS_PER_THREAD);
+    // Shared between the workers, when the last one exits the receiver is dropped and reading stops
+    let rx = Arc::new(Mutex::new(rx));
+    let (recycle, recycled) = mpsc::channel();
+    let stop = AtomicBool::new(false);
+
+    let (read_result, workers) = std::thread::scope(|s| {
+        let handles = (0..num_threads)
+            .map(|t| {
+                let (rx, recycle, placement, stop) =
+                    (Arc::clone(&rx), recycle.clone(), &placement, &stop);
+                s.spawn(move || {
+                    start_worker(t, placement, opts)?;
+                    Ok(aggregate_blocks(&rx, recycle, stop, opts, map_capacity))
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // The workers hold the only references to the receiver now
+        drop(rx);
+        drop(recycle);
+
+        let read_result = read_blocks(reader, tx, recycled, &stop);
+
+        let workers = handles
+            .into_iter()
+            .enumerate()
+            .map(|(i, t)| {
+                let r: Result<Worker> = t.join().expect("Error joining thread");
+                if let Ok(worker) = &r {
+                    let blocks = worker.lines.len();
+                    log!(opts, "Thread {} finished after {blocks} blocks", i + 1);
+                }
+                r
+            })
+            .collect::<Vec<_>>();
+
+        (read_result, workers)
+    });
+
+    let blocks = read_result?;
+    let parse = instant.elapsed();
+    let instant = Instant::now();
+
+    let mut workers = workers.into_iter().collect::<Result<Vec<_>>>()?;
+
+    // Every block before the first bad one has been aggregated, which is all a line number needs. The workers
+    // already counted the lines in each of them, so nothing has to go over the input again.
+    let mut lines = workers
+        .iter_mut()
+        .flat_map(|w| std::mem::take(&mut w.lines))
+        .collect::<Vec<_>>();
+    lines.sort_unstable_by_key(|&(offset, _)| offset);
+    let lines_before = std::iter::once(0)
+        .chain(lines.iter().scan(0, |total, &(_, rows)| {
+            *total += rows as usize;
+            Some(*total)
+        }))
+        .collect::<Vec<_>>();
+    let lines_before = |offset: usize| lines_before[lines.partition_point(|&(o, _)| o < offset)];
+
+    // Blocks are handed out in any order, so report the bad line that comes first in the input
+    if let Some((offset, e)) = workers
+        .iter_mut()
+        .filter_map(|w| w.error.take())
+        .min_by_key(|&(offset, _)| offset)
+    {
+        return Err(e.relocate(offset, lines_before(offset)));
+    }
+
+    let mut maps = Vec::with_capacity(workers.len());
+    let mut rejects = Rejects::default();
+    let mut threads = Vec::with_capacity(workers.len());
+    for worker in workers {
+        for (offset, mut r) in worker.rejects {
+            r.relocate(offset, lines_before(offset));
+            rejects.merge(r);
+        }
+        maps.push(worker.stations);
+        threads.push(worker.timings);
+    }
+
+    let stations = merge_tree(maps, opts.checked)?.into_stations(opts)?;
+
+    let merge = instant.elapsed();
+    log!(
+        opts,
+        "Merging {} threads took {} ms",
+        threads.len(),
+        merge.as_millis()
+    );
+    log!(
+        opts,
+        "====== Streaming {blocks} blocks took {} ms ======",
+        (parse + merge).as_millis()
+    );
+
+    // Blocks were processed out of order
+    rejects
+        .lines
+        .sort_unstable_by_key(|e| e.line_error().map(|e| e.at.offset));
+    rejects.lines.truncate(opts.max_rejects);
+
+    Ok(Stats {
+        stations,
+        decimals: opts.decimals,
+        rejects,
+        timings: Timings {
+            parse,
+            merge,
+            threads,
+            ..Timings::default()
+        },
+        placement,
+    })
+}
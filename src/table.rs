@@ -0,0 +1,297 @@
+// A fixed-capacity open-addressing table built for exactly one job: counting up to MAP_CAPACITY stations
+// Names are raw bytes hashed a word at a time by the same scan that finds the separator, so a row costs a probe and,
+// almost always, one comparison of an inline name
+
+use std::collections::HashMap;
+
+use crate::{accumulate, fx_hash::FxHasher, Error, Result, StationStats, MAP_CAPACITY};
+
+/// Names up to this many bytes are stored in the slot itself, longer ones in the table's arena
+const INLINE_LEN: usize = 16;
+
+/// Enough slots to keep the table at most ~60% full, where linear probing is still short
+const SLOTS: usize = (MAP_CAPACITY * 3 / 2).next_power_of_two();
+/// Slots are picked with the top bits of the hash, the bottom one is used to mark slots as taken
+const SHIFT: u32 = u64::BITS - SLOTS.trailing_zeros();
+/// Most slots a lookup goes through, names that don't find an empty one within that many go with the spilled ones
+///
+/// Far longer than any run random names make at this load, so it only matters for names crafted to share one.
+const MAX_PROBE: usize = 32;
+
+// Per word mixing from rustc's FxHasher, which works on whole words instead of bytes
+const SEED: u64 = 0x517c_c1b7_2722_0a95;
+
+#[inline(always)]
+fn mix(hash: u64, word: u64) -> u64 {
+    (hash.rotate_left(5) ^ word).wrapping_mul(SEED)
+}
+
+/// Avalanche the hash (MurmurHash3's finalizer) so every bit of the name can reach the slot index
+///
+/// Without it, names crafted to share their low hash bits would all probe the same run of slots.
+#[inline(always)]
+fn finish(hash: u64, len: usize) -> u64 {
+    let mut h = hash ^ len as u64;
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^= h >> 33;
+    // Never zero, zero marks an empty slot
+    h | 1
+}
+
+/// Little-endian word starting at `at`, padded with zeros past the end of `bytes`
+#[inline(always)]
+fn load(bytes: &[u8], at: usize) -> u64 {
+    match bytes.get(at..at + 8) {
+        Some(word) => u64::from_le_bytes(word.try_into().unwrap()),
+        None => {
+            let mut word = [0; 8];
+            let rest = &bytes[at..];
+            word[..rest.len()].copy_from_slice(rest);
+            u64::from_le_bytes(word)
+        }
+    }
+}
+
+/// Hash of a station name
+#[inline]
+pub(crate) fn hash_name(name: &[u8]) -> u64 {
+    NameHasher::new(0).finish(name, name.len())
+}
+
+/// [`hash_name`] of a name whose end isn't known yet, fed a word at a time as the scan goes over it
+///
+/// The last word is read whole and masked whenever the input goes on past the name, so only names right at the end
+/// of the input need a copy.
+pub(crate) struct NameHasher {
+    hash: u64,
+    start: usize,
+    /// Start of the next word to mix in
+    at: usize,
+}
+
+impl NameHasher {
+    #[inline(always)]
+    pub(crate) fn new(start: usize) -> Self {
+        Self {
+            hash: 0,
+            start,
+            at: start,
+        }
+    }
+
+    /// Mix in every whole word of `bytes` that ends by `until`, which the name has to go on to at least
+    #[inline(always)]
+    pub(crate) fn update(&mut self, bytes: &[u8], until: usize) {
+        while self.at + 8 <= until {
+            self.hash = mix(self.hash, load(bytes, self.at));
+            self.at += 8;
+        }
+    }
+
+    /// The hash of the name, now that it's known to end at `end`
+    #[inline(always)]
+    pub(crate) fn finish(mut self, bytes: &[u8], end: usize) -> u64 {
+        self.update(bytes, end);
+        if self.at < end {
+            let rest = load(bytes, self.at) & (u64::MAX >> (64 - 8 * (end - self.at)));
+            self.hash = mix(self.hash, rest);
+        }
+        finish(self.hash, end - self.start)
+    }
+}
+
+/// What [`StationTable::add`] did with a measurement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Added {
+    Counted,
+    /// Checking is enabled and the station's sum overflowed
+    Overflowed,
+    /// The station is new and its name wasn't accepted
+    Refused,
+}
+
+#[derive(Clone, Copy)]
+struct Slot {
+    /// Zero if the slot is empty
+    hash: u64,
+    len: u32,
+    /// The name if it fits, otherwise its offset in the arena in the first 8 bytes
+    name: [u8; INLINE_LEN],
+    stats: StationStats,
+}
+
+const EMPTY: Slot = Slot {
+    hash: 0,
+    len: 0,
+    name: [0; INLINE_LEN],
+    stats: StationStats {
+        min: 0,
+        max: 0,
+        sum: 0,
+        count: 0,
+        #[cfg(feature = "stddev")]
+        sum_squares: 0,
+    },
+};
+
+/// Stations of one worker thread
+///
+/// Holds up to [`MAP_CAPACITY`] stations in place, any more than that go into a regular `HashMap` so
+/// inputs breaking the rules are slower but still correct. So do names whose probe run is already [`MAX_PROBE`]
+/// slots long, which keeps names that all hash to the same few slots from turning every lookup into a linear search.
+pub(crate) struct StationTable {
+    slots: Box<[Slot]>,
+    len: usize,
+    /// Names too long to be stored inline, back to back
+    arena: Vec<u8>,
+    spilled: HashMap<Box<[u8]>, StationStats, FxHasher>,
+}
+
+impl StationTable {
+    pub(crate) fn new() -> Self {
+        Self {
+            slots: vec![EMPTY; SLOTS].into_boxed_slice(),
+            len: 0,
+            arena: Vec::new(),
+            spilled: HashMap::default(),
+        }
+    }
+
+    #[inline(always)]
+    fn name<'s>(slots: &'s [Slot], arena: &'s [u8], i: usize) -> &'s [u8] {
+        let slot = &slots[i];
+        let len = slot.len as usize;
+        if len <= INLINE_LEN {
+            &slot.name[..len]
+        } else {
+            let offset = u64::from_le_bytes(slot.name[..8].try_into().unwrap()) as usize;
+            &arena[offset..offset + len]
+        }
+    }
+
+    /// `Ok` with the slot holding `name`, or `Err` with the empty slot where it would go, `None` if there isn't
+    /// one within [`MAX_PROBE`] slots
+    #[inline]
+    fn find(&self, name: &[u8], hash: u64) -> std::result::Result<usize, Option<usize>> {
+        let mut i = (hash >> SHIFT) as usize;
+        for _ in 0..MAX_PROBE {
+            let slot = &self.slots[i];
+            if slot.hash == 0 {
+                return Err(Some(i));
+            }
+            if slot.hash == hash
+                && slot.len as usize == name.len()
+                && Self::name(&self.slots, &self.arena, i) == name
+            {
+                return Ok(i);
+            }
+            i = (i + 1) & (SLOTS - 1);
+        }
+        Err(None)
+    }
+
+    /// Stats of `name` if it's here already, spilled or not, otherwise `Err` with where it would go, as
+    /// [`insert`](Self::insert) takes it
+    #[inline]
+    fn get_mut(
+        &mut self,
+        name: &[u8],
+        hash: u64,
+    ) -> std::result::Result<&mut StationStats, Option<usize>> {
+        match self.find(name, hash) {
+            Ok(i) => Ok(&mut self.slots[i].stats),
+            // Slots are never emptied, so a name with an empty slot in its run was never spilled for a long run
+            Err(Some(i)) if self.len < MAP_CAPACITY => Err(Some(i)),
+            // Only look up, a new name is the only reason to copy it
+            Err(_) => self.spilled.get_mut(name).ok_or(None),
+        }
+    }
+
+    /// Put a new station in the empty slot `i`, or with the spilled ones if there's no slot for it or the table is
+    /// at capacity
+    ///
+    /// Returns the stats that are now stored for it.
+    fn insert(&mut self, i: Option<usize>, name: &[u8], hash: u64) -> &mut StationStats {
+        let Some(i) = i.filter(|_| self.len < MAP_CAPACITY) else {
+            return self.spilled.entry(name.into()).or_insert(EMPTY.stats);
+        };
+
+        let slot = &mut self.slots[i];
+        slot.hash = hash;
+        slot.len = name.len() as u32;
+        if name.len() <= INLINE_LEN {
+            slot.name[..name.len()].copy_from_slice(name);
+        } else {
+            slot.name[..8].copy_from_slice(&(self.arena.len() as u64).to_le_bytes());
+            self.arena.extend_from_slice(name);
+        }
+        self.len += 1;
+        &mut slot.stats
+    }
+
+    /// Add a measurement for `name`, whose [`hash_name`] is `hash`
+    ///
+    /// A station that isn't here yet is only added if `accept` allows its name.
+    #[inline]
+    pub(crate) fn add(
+        &mut self,
+        name: &[u8],
+        hash: u64,
+        temp: i32,
+        checked: bool,
+        accept: impl FnOnce(&[u8]) -> bool,
+    ) -> Added {
+        let counted = match self.get_mut(name, hash) {
+            Ok(stats) => accumulate(stats, temp, checked),
+            Err(_) if !accept(name) => return Added::Refused,
+            Err(i) => {
+                *self.insert(i, name, hash) = StationStats::new(temp);
+                true
+            }
+        };
+        if counted {
+            Added::Counted
+        } else {
+            Added::Overflowed
+        }
+    }
+
+    /// Every station with its stats, spilled ones included
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (&[u8], &StationStats)> {
+        let in_place = (0..SLOTS).filter(|&i| self.slots[i].hash != 0).map(|i| {
+            (
+                Self::name(&self.slots, &self.arena, i),
+                &self.slots[i].stats,
+            )
+        });
+        let spilled = self.spilled.iter().map(|(name, stats)| (&**name, stats));
+        in_place.chain(spilled)
+    }
+
+    /// Merge the stations of another thread into this one
+    pub(crate) fn merge(&mut self, other: Self, checked: bool) -> Result<()> {
+        for (name, stats) in other.entries() {
+            let hash = hash_name(name);
+            let own = match self.get_mut(name, hash) {
+                Ok(own) => own,
+                Err(i) => self.insert(i, name, hash),
+            };
+            if own.count == 0 {
+                *own = *stats;
+            } else if !checked {
+                own.merge(stats);
+            } else {
+                match own.checked_merge(stats) {
+                    Some(merged) => *own = merged,
+                    None => {
+                        return Err(Error::Overflow(String::from_utf8_lossy(name).into_owned()))
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
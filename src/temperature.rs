@@ -0,0 +1,142 @@
+// Parsing measurements straight into fixed-point integers, without going through floats
+// Pretty much every line in the challenge looks like `-?\d{1,2}\.\d` so that gets a fast path, anything else takes the slow one
+
+const LANES: u64 = 0x0101_0101_0101_0101;
+
+/// Most decimals a measurement can be stored with, more would make the integer part too small to be useful
+pub const MAX_DECIMALS: u8 = 4;
+
+/// What to do with values that have more decimals than configured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExcessPrecision {
+    /// Round half away from zero
+    #[default]
+    Round,
+    /// Treat the value as invalid, unless the extra digits are all zero
+    Reject,
+}
+
+/// Parse a temperature like `-12.3` into tenths of a degree (`-123`)
+///
+/// Surrounding whitespace, a leading `+`, a missing fractional part and extra fractional digits are all accepted,
+/// extra digits are rounded half away from zero. Returns `None` for anything that isn't a plain decimal number.
+#[inline]
+pub fn parse_tenths(bytes: &[u8]) -> Option<i32> {
+    parse_fixed(bytes, 1, ExcessPrecision::Round)
+}
+
+/// Parse a measurement into a fixed-point integer with `decimals` digits after the point (`-12.3` is `-1230` with 2 decimals)
+///
+/// Accepts the same shapes as [`parse_tenths`], `excess` decides what happens to digits beyond `decimals`.
+#[inline]
+pub fn parse_fixed(bytes: &[u8], decimals: u8, excess: ExcessPrecision) -> Option<i32> {
+    debug_assert!((1..=MAX_DECIMALS).contains(&decimals));
+    if decimals == 1 {
+        if let Some(value) = parse_fast(bytes) {
+            return Some(value);
+        }
+    }
+    parse_slow(bytes, decimals as usize, excess)
+}
+
+/// SWAR parser for exactly `-?\d{1,2}\.\d`
+///
+/// Based on the approach from merykitty's Java submission: load the whole number into one word,
+/// line the digits up and let a single multiplication add them together with the right weights.
+#[inline]
+fn parse_fast(bytes: &[u8]) -> Option<i32> {
+    let len = bytes.len();
+    if !(3..=5).contains(&len) {
+        return None;
+    }
+
+    let mut buf = [0; 8];
+    buf[..len].copy_from_slice(bytes);
+    let word = u64::from_le_bytes(buf);
+
+    let negative = (bytes[0] == b'-') as usize;
+    let dot = len - 2;
+
+    // 1 or 2 integer digits, then the dot, then a single fractional digit
+    if dot - negative == 0 || dot - negative > 2 || bytes[dot] != b'.' {
+        return None;
+    }
+
+    // 0xFF in every byte that has to be a digit
+    let digit_lanes =
+        (u64::MAX >> (64 - 8 * len)) & !(0xFF << (8 * dot)) & !(negative as u64 * 0xFF);
+
+    // A byte is a digit if its high nibble is 3 and adding 6 to it doesn't change that
+    let high = 0xF0 * LANES;
+    let expected = (0x30 * LANES) & digit_lanes;
+    let not_digits = ((word & high & digit_lanes) ^ expected)
+        | ((word.wrapping_add(0x06 * LANES) & high & digit_lanes) ^ expected);
+    if not_digits != 0 {
+        return None;
+    }
+
+    // The dot's 0x10 bit, which is clear unlike in every digit
+    let dot_bit = 8 * dot as u32 + 4;
+    let shift = 28 - dot_bit;
+    // All ones if negative, since '-' is the only other byte with the 0x10 bit clear
+    let sign = ((!word << 59) as i64 >> 63) as u64;
+    let without_sign = word & !(sign & 0xFF);
+    // Line the digits up as `0x0D000D0D00` (tens, ones, tenths) no matter how many integer digits there were
+    let digits = (without_sign << shift) & 0x0F_000F_0F00;
+    // Multiplying by 100 * tens + 10 * ones + tenths lands the sum in bits 32..42
+    let abs = (digits.wrapping_mul(0x640A_0001) >> 32) & 0x3FF;
+
+    Some(((abs ^ sign).wrapping_sub(sign)) as i64 as i32)
+}
+
+/// General parser for anything [`parse_fast`] doesn't take
+///
+/// Not `#[cold]`: with more than one decimal every row comes through here.
+fn parse_slow(bytes: &[u8], decimals: usize, excess: ExcessPrecision) -> Option<i32> {
+    let bytes = bytes.trim_ascii();
+
+    let (negative, bytes) = match bytes.first()? {
+        b'-' => (true, &bytes[1..]),
+        b'+' => (false, &bytes[1..]),
+        _ => (false, bytes),
+    };
+
+    let (int_part, frac_part) = match bytes.iter().position(|&b| b == b'.') {
+        Some(i) => (&bytes[..i], &bytes[i + 1..]),
+        None => (bytes, &[][..]),
+    };
+
+    if int_part.is_empty() && frac_part.is_empty() {
+        return None;
+    }
+    if !int_part.iter().chain(frac_part).all(u8::is_ascii_digit) {
+        return None;
+    }
+
+    let mut value: i32 = 0;
+    let digits = int_part.iter().chain(
+        frac_part
+            .iter()
+            .chain(std::iter::repeat(&b'0'))
+            .take(decimals),
+    );
+    for &d in digits {
+        value = value.checked_mul(10)?.checked_add((d - b'0') as i32)?;
+    }
+
+    let extra = frac_part.get(decimals..).unwrap_or_default();
+    match excess {
+        ExcessPrecision::Round => {
+            if extra.first().is_some_and(|&d| d >= b'5') {
+                value = value.checked_add(1)?;
+            }
+        }
+        ExcessPrecision::Reject => {
+            if extra.iter().any(|&d| d != b'0') {
+                return None;
+            }
+        }
+    }
+
+    Some(if negative { -value } else { value })
+}
@@ -0,0 +1,92 @@
+// UTF-8 validation of station names
+// Names are checked when a thread first sees them, so this only runs once per station per thread. ASCII is skipped
+// 16 bytes at a time, anything else goes through a branchless shift-based DFA, one table lookup per byte.
+
+/// DFA states, as the bit offset of their next state within a row of [`TRANSITIONS`]
+const ACCEPT: u32 = 0;
+const REJECT: u32 = 6;
+/// Waiting for this many continuation bytes
+const CONT1: u32 = 12;
+const CONT2: u32 = 18;
+const CONT3: u32 = 24;
+/// After a lead byte whose next byte has a narrower range, to rule out overlong encodings, surrogates and
+/// code points past U+10FFFF
+const AFTER_E0: u32 = 30;
+const AFTER_ED: u32 = 36;
+const AFTER_F0: u32 = 42;
+const AFTER_F4: u32 = 48;
+
+const STATES: [u32; 9] = [
+    ACCEPT, REJECT, CONT1, CONT2, CONT3, AFTER_E0, AFTER_ED, AFTER_F0, AFTER_F4,
+];
+
+/// State after reading `byte` in `state`, straight from the table in the Unicode standard (3.9, table 3-7)
+const fn next(state: u32, byte: u8) -> u32 {
+    // Every state but the first wants one continuation byte in some range
+    let (lo, hi, then) = match state {
+        ACCEPT => {
+            return match byte {
+                0x00..=0x7F => ACCEPT,
+                0xC2..=0xDF => CONT1,
+                0xE0 => AFTER_E0,
+                0xE1..=0xEC | 0xEE..=0xEF => CONT2,
+                0xED => AFTER_ED,
+                0xF0 => AFTER_F0,
+                0xF1..=0xF3 => CONT3,
+                0xF4 => AFTER_F4,
+                _ => REJECT,
+            }
+        }
+        CONT1 => (0x80, 0xBF, ACCEPT),
+        CONT2 => (0x80, 0xBF, CONT1),
+        CONT3 => (0x80, 0xBF, CONT2),
+        AFTER_E0 => (0xA0, 0xBF, CONT1),
+        AFTER_ED => (0x80, 0x9F, CONT1),
+        AFTER_F0 => (0x90, 0xBF, CONT2),
+        AFTER_F4 => (0x80, 0x8F, CONT2),
+        _ => return REJECT,
+    };
+    if byte >= lo && byte <= hi {
+        then
+    } else {
+        REJECT
+    }
+}
+
+/// For every byte, the next state of every state packed into one word: `TRANSITIONS[byte] >> state & 63`
+const TRANSITIONS: [u64; 256] = {
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut i = 0;
+        while i < STATES.len() {
+            table[byte] |= (next(STATES[i], byte as u8) as u64) << STATES[i];
+            i += 1;
+        }
+        byte += 1;
+    }
+    table
+};
+
+/// Whether `bytes` is valid UTF-8
+pub(crate) fn is_valid(bytes: &[u8]) -> bool {
+    let mut state = ACCEPT;
+    let mut blocks = bytes.chunks_exact(16);
+    for block in &mut blocks {
+        // Written so it compiles down to one vector compare per block
+        let ascii = block.iter().fold(0, |acc, &b| acc | b) < 0x80;
+        if ascii && state == ACCEPT {
+            continue;
+        }
+        for &b in block {
+            state = (TRANSITIONS[b as usize] >> state) as u32 & 63;
+        }
+        if state == REJECT {
+            return false;
+        }
+    }
+    for &b in blocks.remainder() {
+        state = (TRANSITIONS[b as usize] >> state) as u32 & 63;
+    }
+    state == ACCEPT
+}
@@ -0,0 +1,123 @@
+use std::num::NonZeroUsize;
+
+use onebrc_rs::{aggregate_bytes, Error, Options, StationStats, Sum};
+
+fn repeated(line: &str, times: usize) -> Vec<u8> {
+    line.repeat(times).into_bytes()
+}
+
+#[test]
+fn sum_past_i32() {
+    // 2.2 million readings of 99.9 is 2,197,800,000 tenths, which used to wrap around
+    let input = repeated("Dallol;99.9\n", 2_200_000);
+
+    for threads in [1, 3] {
+        let opts = Options {
+            threads: NonZeroUsize::new(threads),
+            checked: true,
+            ..Options::default()
+        };
+        let stats = aggregate_bytes(&input, &opts).unwrap();
+        let dallol = &stats.stations["Dallol"];
+
+        assert_eq!(dallol.sum(), 2_197_800_000);
+        assert_eq!(dallol.count, 2_200_000);
+        assert_eq!(dallol.mean(), 999);
+    }
+}
+
+#[test]
+fn negative_sum_past_i32() {
+    let input = repeated("Vostok;-89.2\n", 2_500_000);
+    let opts = Options {
+        threads: NonZeroUsize::new(2),
+        ..Options::default()
+    };
+    let stats = aggregate_bytes(&input, &opts).unwrap();
+    let vostok = &stats.stations["Vostok"];
+
+    assert_eq!(vostok.sum(), -2_230_000_000);
+    assert_eq!(vostok.mean(), -892);
+}
+
+/// `temp` merged with itself `times` times over, so 2^`times` measurements of it
+fn doubled(temp: i32, times: u32) -> StationStats {
+    let mut stats = StationStats::new(temp);
+    for _ in 0..times {
+        stats = stats.checked_merge(&stats).unwrap();
+    }
+    stats
+}
+
+#[test]
+fn checked_add_overflow() {
+    // (2^30 - 1) * 2^33 and four more of i32::MAX comes to 3 short of i64::MAX
+    let mut stats = doubled((1 << 30) - 1, 33);
+    for _ in 0..4 {
+        stats.add(i32::MAX);
+    }
+    assert_eq!(stats.sum(), i64::MAX as Sum - 3);
+
+    assert_eq!(stats.checked_add(3).map(|s| s.sum()), Some(i64::MAX as Sum));
+    // Only the accumulator is an i64 without the i128-sum feature, the sum it's read as never is
+    let past = stats.checked_add(4).map(|s| s.sum());
+    assert_eq!(
+        past,
+        cfg!(feature = "i128-sum").then_some(i64::MAX as Sum + 1)
+    );
+
+    let low = doubled(-(1 << 30), 33);
+    assert_eq!(low.sum(), i64::MIN as Sum);
+    let past = low.checked_add(-1).map(|s| s.sum());
+    assert_eq!(
+        past,
+        cfg!(feature = "i128-sum").then_some(i64::MIN as Sum - 1)
+    );
+}
+
+#[test]
+fn checked_merge_overflow() {
+    let big = doubled(1 << 30, 32);
+    assert_eq!(big.sum(), 1 << 62);
+
+    assert_eq!(
+        big.checked_merge(&big).is_some(),
+        cfg!(feature = "i128-sum")
+    );
+    assert_eq!(
+        big.checked_merge(&StationStats::new(-999)).map(|s| s.count),
+        Some((1 << 32) + 1)
+    );
+    let mut full = StationStats::new(1);
+    full.count = u64::MAX;
+    assert_eq!(full.checked_merge(&StationStats::new(1)), None);
+}
+
+#[test]
+fn overflow_error_names_station() {
+    let e = Error::Overflow("Dallol".to_string());
+    assert!(e.to_string().contains("'Dallol'"));
+}
+
+#[test]
+fn merge_keeps_both_extremes() {
+    let mut a = StationStats::new(0);
+    let mut b = StationStats::new(-50);
+    b.add(50);
+    a.merge(&b);
+
+    assert_eq!((a.min, a.max, a.count), (-50, 50, 3));
+}
+
+#[cfg(feature = "stddev")]
+#[test]
+fn stddev_past_i64_squares() {
+    // 99.9999 and -99.9999 at 4 decimals, 16.7 million rows of them square up to past i64::MAX
+    let mut stats = StationStats::new(999_999).checked_add(-999_999).unwrap();
+    for _ in 0..23 {
+        stats = stats.checked_merge(&stats).unwrap();
+    }
+    assert_eq!(stats.count, 1 << 24);
+    assert!(stats.sum_squares() > i64::MAX as i128);
+    assert_eq!((stats.mean(), stats.stddev()), (0, 999_999));
+}
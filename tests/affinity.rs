@@ -0,0 +1,118 @@
+mod common;
+
+use std::{fs, num::NonZeroUsize, path::PathBuf};
+
+use onebrc_rs::{
+    affinity::{parse_cpu_list, Node, Placement, Topology},
+    aggregate_bytes, Options,
+};
+
+#[test]
+fn cpu_lists() {
+    assert_eq!(parse_cpu_list("0\n"), Some(vec![0]));
+    assert_eq!(parse_cpu_list("0-3"), Some(vec![0, 1, 2, 3]));
+    assert_eq!(parse_cpu_list("0-1,8-9,16\n"), Some(vec![0, 1, 8, 9, 16]));
+    assert_eq!(parse_cpu_list(""), Some(vec![]));
+    assert_eq!(parse_cpu_list("3-1"), None);
+    assert_eq!(parse_cpu_list("0,,1"), None);
+    assert_eq!(parse_cpu_list("x"), None);
+}
+
+/// A made-up `/sys/devices/system/node`, removed on drop
+struct FakeSysfs(PathBuf);
+
+impl FakeSysfs {
+    fn new(name: &str, nodes: &[(&str, &str)]) -> Self {
+        let root = std::env::temp_dir().join(format!("onebrc-{name}-{}", std::process::id()));
+        for (node, cpus) in nodes {
+            fs::create_dir_all(root.join(node)).unwrap();
+            fs::write(root.join(node).join("cpulist"), cpus).unwrap();
+        }
+        // Not every entry is a node
+        fs::create_dir_all(root.join("power")).unwrap();
+        fs::write(root.join("possible"), "0-1\n").unwrap();
+        Self(root)
+    }
+}
+
+impl Drop for FakeSysfs {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn nodes_from_sysfs() {
+    let sysfs = FakeSysfs::new(
+        "nodes",
+        &[("node1", "4-7\n"), ("node0", "0-3\n"), ("node2", "8-11\n")],
+    );
+    // Only some CPUs are ours, and none of node 2's
+    let topology = Topology::from_sysfs(&sysfs.0, &[1, 2, 3, 5, 6]).unwrap();
+    assert_eq!(
+        topology.nodes,
+        [
+            Node {
+                id: 0,
+                cpus: vec![1, 2, 3]
+            },
+            Node {
+                id: 1,
+                cpus: vec![5, 6]
+            },
+        ]
+    );
+
+    // Without NUMA it's all one node
+    let missing = sysfs.0.join("missing");
+    assert_eq!(
+        Topology::from_sysfs(&missing, &[0, 1]).unwrap().nodes,
+        [Node {
+            id: 0,
+            cpus: vec![0, 1]
+        }]
+    );
+}
+
+#[test]
+fn spreads_threads_over_nodes() {
+    let topology = Topology {
+        nodes: vec![
+            Node {
+                id: 0,
+                cpus: vec![0, 1, 2],
+            },
+            Node {
+                id: 1,
+                cpus: vec![8],
+            },
+        ],
+    };
+    let placement = topology.place(6);
+    let cpus = placement.iter().map(|p| p.cpu).collect::<Vec<_>>();
+    assert_eq!(cpus, [0, 8, 1, 2, 0, 8]);
+    assert_eq!(placement[1], Placement { cpu: 8, node: 1 });
+    assert!(topology.place(0).is_empty());
+}
+
+#[test]
+fn pinned_runs_agree() {
+    let input = (0..20_000)
+        .map(|i| format!("station {};{}.{}\n", i % 101, i % 60 - 20, i % 10))
+        .collect::<String>();
+    let unpinned = aggregate_bytes(input.as_bytes(), &Options::default()).unwrap();
+    assert!(unpinned.placement.is_empty());
+
+    for threads in [1, 4] {
+        let opts = Options {
+            threads: NonZeroUsize::new(threads),
+            chunk_size: 4096,
+            pin: true,
+            ..Options::default()
+        };
+        for stats in common::aggregate_both(input.as_bytes(), &opts) {
+            assert_eq!(stats.placement.len(), threads);
+            assert_eq!(stats.sorted(), unpinned.sorted());
+        }
+    }
+}
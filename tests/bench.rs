@@ -0,0 +1,28 @@
+use std::process::Command;
+
+#[test]
+fn json_report() {
+    let output = Command::new(env!("CARGO_BIN_EXE_onebrc-rs"))
+        .args([
+            "bench", "--quiet", "--json", "--rows", "10000", "--runs", "3",
+        ])
+        .args(["--warmup", "0", "--threads", "2", "classic", "zipf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let json = String::from_utf8(output.stdout).unwrap();
+    assert!(json.starts_with(
+        "{\"runs\":3,\"warmup\":0,\"evict\":false,\"pin\":false,\"inputs\":[{\"name\":\"classic\""
+    ));
+    assert!(json.contains("\"name\":\"zipf\""));
+    assert!(json.contains("\"rows\":10000,\"stations\":413"));
+    for phase in ["map", "chunk", "parse", "merge", "sort", "print", "total"] {
+        assert_eq!(
+            json.matches(&format!("\"{phase}\":{{\"min_ms\":")).count(),
+            2,
+            "{phase}"
+        );
+    }
+    assert_eq!(json.matches("\"rows_per_sec\":").count(), 4);
+}
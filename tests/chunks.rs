@@ -0,0 +1,242 @@
+mod common;
+
+use std::{
+    io::Read,
+    num::NonZeroUsize,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use onebrc_rs::{aggregate_bytes, aggregate_reader, Error, ErrorPolicy, Options};
+
+fn input(rows: usize) -> String {
+    (0..rows)
+        .map(|i| format!("station {};{}.{}\n", i % 101, i % 60, i % 10))
+        .collect()
+}
+
+#[test]
+fn any_chunk_size() {
+    let input = input(5_000);
+    let expected = common::aggregate(&input, ';', 1);
+
+    // Smaller than a line, a few lines, not a multiple of anything, and more than the whole input
+    for chunk_size in [1, 5, 64, 1_000, 4_099, 1 << 20] {
+        for threads in [1, 3, 8] {
+            let opts = Options {
+                threads: NonZeroUsize::new(threads),
+                chunk_size,
+                ..Options::default()
+            };
+            let stats = aggregate_bytes(input.as_bytes(), &opts).unwrap();
+            common::assert_matches(
+                &stats.stations,
+                &expected,
+                &format!("{chunk_size} byte chunks and {threads} threads"),
+            );
+            let rows = stats.timings.threads.iter().map(|t| t.rows).sum::<u64>();
+            assert_eq!(rows, 5_000);
+        }
+    }
+}
+
+#[test]
+fn huge_chunk_sizes() {
+    let input = input(1_000);
+    let expected = common::aggregate(&input, ';', 1);
+
+    // Big enough that adding one per thread overflows, which must not hand out the start of the input again
+    for chunk_size in [usize::MAX, usize::MAX / 2 + 1, usize::MAX / 8 + 1] {
+        let opts = Options {
+            threads: NonZeroUsize::new(16),
+            chunk_size,
+            ..Options::default()
+        };
+        let stats = aggregate_bytes(input.as_bytes(), &opts).unwrap();
+        common::assert_matches(
+            &stats.stations,
+            &expected,
+            &format!("{chunk_size} byte chunks"),
+        );
+    }
+}
+
+#[test]
+fn rejects_point_at_the_right_lines() {
+    let mut lines = input(2_000).lines().map(String::from).collect::<Vec<_>>();
+    let bad = [0, 17, 640, 641, 1_500, 1_999];
+    for &i in &bad {
+        lines[i] = format!("bad line {i}");
+    }
+    let input = lines.join("\n");
+
+    for chunk_size in [7, 300, 1 << 20] {
+        let opts = Options {
+            threads: NonZeroUsize::new(4),
+            chunk_size,
+            on_error: ErrorPolicy::Report,
+            ..Options::default()
+        };
+        let stats = aggregate_bytes(input.as_bytes(), &opts).unwrap();
+        let at = stats
+            .rejects
+            .lines
+            .iter()
+            .map(|e| {
+                let e = e.line_error().unwrap();
+                (e.at.line, e.snippet().to_string())
+            })
+            .collect::<Vec<_>>();
+        let expected = bad
+            .iter()
+            .map(|&i| (i as u64 + 1, format!("bad line {i}")))
+            .collect::<Vec<_>>();
+        assert_eq!(at, expected, "{chunk_size} byte chunks");
+    }
+}
+
+#[test]
+fn fails_on_the_first_bad_line() {
+    let mut lines = input(3_000).lines().map(String::from).collect::<Vec<_>>();
+    lines[1_234] = "first;bad".into();
+    lines[2_500] = "second;bad".into();
+    let input = lines.join("\n");
+
+    let opts = Options {
+        threads: NonZeroUsize::new(4),
+        chunk_size: 100,
+        ..Options::default()
+    };
+    match aggregate_bytes(input.as_bytes(), &opts) {
+        Err(Error::BadTemperature(e)) => {
+            assert_eq!((e.at.line, e.snippet()), (1_235, "first;bad"))
+        }
+        other => panic!("{other:?}"),
+    }
+}
+
+#[test]
+fn streamed_rejects_across_blocks() {
+    // About 12 MB, so the reader hands out a few blocks and line numbers have to carry over from one to the next
+    let mut lines = input(600_000).lines().map(String::from).collect::<Vec<_>>();
+    let bad = [3, 250_000, 250_001, 420_000, 599_999];
+    for &i in &bad {
+        lines[i] = format!("bad;line {i}");
+    }
+    let input = lines.join("\n");
+
+    let opts = Options {
+        threads: NonZeroUsize::new(3),
+        on_error: ErrorPolicy::Report,
+        ..Options::default()
+    };
+    let at = |stats: onebrc_rs::Stats| {
+        stats
+            .rejects
+            .lines
+            .iter()
+            .map(|e| e.line_error().unwrap().at)
+            .collect::<Vec<_>>()
+    };
+    let streamed = at(aggregate_reader(input.as_bytes(), &opts).unwrap());
+    assert_eq!(
+        streamed,
+        at(aggregate_bytes(input.as_bytes(), &opts).unwrap())
+    );
+    assert_eq!(
+        streamed.iter().map(|at| at.line).collect::<Vec<_>>(),
+        bad.map(|i| i as u64 + 1)
+    );
+
+    let opts = Options {
+        on_error: ErrorPolicy::Fail,
+        ..opts
+    };
+    match aggregate_reader(input.as_bytes(), &opts) {
+        Err(Error::BadTemperature(e)) => assert_eq!(e.at, streamed[0]),
+        other => panic!("{other:?}"),
+    }
+    // Past the first block, the one before it has to have been counted
+    lines[3] = "station 3;3.3".into();
+    let input = lines.join("\n");
+    match aggregate_reader(input.as_bytes(), &opts) {
+        Err(Error::BadTemperature(e)) => {
+            assert_eq!((e.at.line, e.snippet()), (250_001, "bad;line 250000"))
+        }
+        other => panic!("{other:?}"),
+    }
+}
+
+#[test]
+fn streaming_stops_at_a_bad_line() {
+    // A gigabyte of good lines after a bad one, counting how much of them gets read
+    struct Good<'a> {
+        read: &'a AtomicUsize,
+    }
+    impl Read for Good<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            const LINE: &[u8] = b"station;12.3\n";
+            let at = self.read.fetch_add(buf.len(), Ordering::Relaxed);
+            if at >= 1 << 30 {
+                return Ok(0);
+            }
+            for (i, b) in buf.iter_mut().enumerate() {
+                *b = LINE[(at + i) % LINE.len()];
+            }
+            Ok(buf.len())
+        }
+    }
+
+    let read = AtomicUsize::new(0);
+    let input = "station;bad\n".as_bytes().chain(Good { read: &read });
+    let opts = Options {
+        threads: NonZeroUsize::new(3),
+        ..Options::default()
+    };
+    match aggregate_reader(input, &opts) {
+        Err(Error::BadTemperature(e)) => assert_eq!(e.at.line, 1),
+        other => panic!("{other:?}"),
+    }
+    // The channel and the blocks being worked on are all that can be read past the bad line
+    let read = read.load(Ordering::Relaxed);
+    assert!(read < 64 * 1024 * 1024, "read {read} bytes");
+}
+
+#[test]
+fn tiny_and_odd_inputs() {
+    let cases = [
+        ("", vec![]),
+        ("\n", vec![]),
+        ("a;1.0", vec![("a", 10)]),
+        ("a;1.0\nb;-2.5", vec![("a", 10), ("b", -25)]),
+        ("a;1.0\r\nb;-2.5\r\n", vec![("a", 10), ("b", -25)]),
+        ("a;1.0\r\nb;-2.5", vec![("a", 10), ("b", -25)]),
+        ("a;1.0\r\nb;-2.5\r", vec![("a", 10), ("b", -25)]),
+    ];
+
+    for (input, expected) in cases {
+        for threads in [1, 2, 16] {
+            for chunk_size in [1, 3, 1 << 20] {
+                let opts = Options {
+                    threads: NonZeroUsize::new(threads),
+                    chunk_size,
+                    on_error: ErrorPolicy::Skip,
+                    ..Options::default()
+                };
+                for stats in common::aggregate_both(input.as_bytes(), &opts) {
+                    let mut got = stats
+                        .stations
+                        .iter()
+                        .map(|(name, s)| (name.as_str(), s.min))
+                        .collect::<Vec<_>>();
+                    got.sort();
+                    assert_eq!(
+                        got, expected,
+                        "{input:?} with {threads} threads and {chunk_size} byte chunks"
+                    );
+                    // A lone newline is one empty line, nothing else should be rejected
+                    assert_eq!(stats.rejects.total(), (input == "\n") as u64, "{input:?}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+/// Run the binary with `args` in `dir`
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_onebrc-rs"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// A fresh directory holding `file` with a couple of measurements in it
+fn dir_with(name: &str, file: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("onebrc-cli-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join(file), "Oslo;1.0\nOslo;3.0\nLima;20.5\n").unwrap();
+    dir
+}
+
+#[test]
+fn bad_arguments_exit_with_2() {
+    let dir = dir_with("bad", "measurements.txt");
+    for args in [
+        &["--bogus"][..],
+        &["-x"],
+        &["--threads"],
+        &["--format"],
+        &["--threads", "0"],
+        &["--threads=0"],
+        &["--threads", "many"],
+        &["measurements.txt", "other.txt"],
+        // Latin-1 has nothing past U+00FF, whichever order the options come in
+        &["--encoding", "latin1", "--separator", "→"],
+        &["--separator=→", "--encoding=latin1"],
+        &["generate", "--bogus"],
+        &["generate", "--seed"],
+        &["generate", "--threads", "0"],
+        &["bench", "--runs"],
+        &["bench", "--threads", "0"],
+    ] {
+        let output = run(&dir, args);
+        assert_eq!(output.status.code(), Some(2), "{args:?}: {output:?}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.starts_with("error: "), "{args:?}: {stderr}");
+        assert!(stderr.contains("Usage: onebrc-rs"), "{args:?}: {stderr}");
+        assert!(output.stdout.is_empty(), "{args:?}");
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn help_exits_with_0() {
+    let dir = std::env::temp_dir();
+    for (args, usage) in [
+        (&["--help"][..], "Usage: onebrc-rs [OPTIONS] [FILE]"),
+        (&["-h"], "Usage: onebrc-rs [OPTIONS] [FILE]"),
+        // Even after options that would fail to run
+        (
+            &["missing.txt", "--help"],
+            "Usage: onebrc-rs [OPTIONS] [FILE]",
+        ),
+        (&["generate", "--help"], "Usage: onebrc-rs generate"),
+        (&["bench", "-h"], "Usage: onebrc-rs bench"),
+    ] {
+        let output = run(&dir, args);
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.starts_with(usage), "{args:?}: {stdout}");
+    }
+}
+
+#[test]
+fn inline_values_and_positional_separator() {
+    let dir = dir_with("inline", "-measurements.txt");
+    let expected = "name,count\r\nLima,1\r\nOslo,2\r\n";
+
+    let spaced = run(
+        &dir,
+        &[
+            "--quiet",
+            "--threads",
+            "2",
+            "--format",
+            "csv",
+            "--columns",
+            "name,count",
+            "--",
+            "-measurements.txt",
+        ],
+    );
+    assert!(spaced.status.success(), "{spaced:?}");
+    assert_eq!(String::from_utf8_lossy(&spaced.stdout), expected);
+
+    let inline = run(
+        &dir,
+        &[
+            "-q",
+            "--threads=2",
+            "--format=csv",
+            "--columns=name,count",
+            "--",
+            "-measurements.txt",
+        ],
+    );
+    assert!(inline.status.success(), "{inline:?}");
+    assert_eq!(String::from_utf8_lossy(&inline.stdout), expected);
+
+    // Without the `--` it's an option
+    let output = run(&dir, &["-q", "-measurements.txt"]);
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn failed_runs_exit_with_1() {
+    let dir = dir_with("fail", "measurements.txt");
+    std::fs::write(dir.join("bad.txt"), "Oslo;warm\n").unwrap();
+    for args in [&["-q", "missing.txt"][..], &["-q", "bad.txt"]] {
+        let output = run(&dir, args);
+        assert_eq!(output.status.code(), Some(1), "{args:?}: {output:?}");
+        assert!(output.stdout.is_empty(), "{args:?}");
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
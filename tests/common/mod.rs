@@ -0,0 +1,163 @@
+// Shared between the integration tests: a deliberately naive reference aggregator and a tiny PRNG,
+// so randomized tests don't need any crates
+
+#![allow(dead_code)]
+
+use std::collections::{BTreeMap, HashMap};
+
+use onebrc_rs::{aggregate_bytes, aggregate_reader, Options, StationStats, Stats};
+
+/// What the reference aggregator keeps per station, with a sum that can't overflow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reference {
+    pub min: i64,
+    pub max: i64,
+    pub sum: i128,
+    pub count: u64,
+}
+
+impl Reference {
+    /// Mean rounded half towards positive infinity, like `Math.round` in the reference implementation
+    pub fn mean(&self) -> i64 {
+        let count = self.count as i128;
+        (2 * self.sum + count).div_euclid(2 * count) as i64
+    }
+}
+
+/// Parse a value with exactly `decimals` digits after the point into a fixed-point integer
+fn parse(value: &str, decimals: u8) -> i64 {
+    let (int, frac) = value.split_once('.').expect("reference input has a dot");
+    assert_eq!(frac.len(), decimals as usize, "unexpected value {value}");
+    let negative = int.starts_with('-');
+    let digits = format!("{}{frac}", int.trim_start_matches('-'));
+    let abs = digits.parse::<i64>().expect("reference input is numeric");
+    if negative {
+        -abs
+    } else {
+        abs
+    }
+}
+
+/// Aggregate well-formed input one line at a time on a single thread
+pub fn aggregate(input: &str, separator: char, decimals: u8) -> BTreeMap<String, Reference> {
+    let mut stations = BTreeMap::<String, Reference>::new();
+    for line in input.lines() {
+        let (name, value) = line
+            .split_once(separator)
+            .expect("reference input is valid");
+        let value = parse(value, decimals);
+        stations
+            .entry(name.to_string())
+            .and_modify(|s| {
+                s.min = s.min.min(value);
+                s.max = s.max.max(value);
+                s.sum += value as i128;
+                s.count += 1;
+            })
+            .or_insert(Reference {
+                min: value,
+                max: value,
+                sum: value as i128,
+                count: 1,
+            });
+    }
+    stations
+}
+
+/// Assert that `stations` is exactly the reference's `expected`, `context` says which run it was
+pub fn assert_matches(
+    stations: &HashMap<String, StationStats>,
+    expected: &BTreeMap<String, Reference>,
+    context: &str,
+) {
+    assert_eq!(
+        stations.len(),
+        expected.len(),
+        "wrong number of stations: {context}"
+    );
+    for (name, want) in expected {
+        let got = stations
+            .get(name)
+            .unwrap_or_else(|| panic!("missing station {name:?}: {context}"));
+        assert_eq!(
+            (
+                got.min as i64,
+                got.mean() as i64,
+                got.max as i64,
+                got.sum(),
+                got.count
+            ),
+            (want.min, want.mean(), want.max, want.sum, want.count),
+            "{name:?}: {context}"
+        );
+    }
+}
+
+/// What the library makes of `input` both mapped and streamed, which should always agree
+pub fn aggregate_both(input: &[u8], opts: &Options) -> [Stats; 2] {
+    [
+        aggregate_bytes(input, opts).unwrap(),
+        aggregate_reader(input, opts).unwrap(),
+    ]
+}
+
+/// Format a fixed-point value with `decimals` digits after the point, without going through floats
+pub fn format_value(value: i64, decimals: u8) -> String {
+    let scale = 10i64.pow(decimals as u32);
+    let sign = if value < 0 { "-" } else { "" };
+    let abs = value.unsigned_abs();
+    format!(
+        "{sign}{}.{:0w$}",
+        abs / scale as u64,
+        abs % scale as u64,
+        w = decimals as usize
+    )
+}
+
+/// SplitMix64, small and good enough for generating test data
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Uniform in `lo..=hi`
+    pub fn range(&mut self, lo: i64, hi: i64) -> i64 {
+        lo + (self.next_u64() % (hi - lo + 1) as u64) as i64
+    }
+
+    pub fn chance(&mut self, p: f64) -> bool {
+        self.unit() < p
+    }
+
+    /// Uniform in `[0, 1)`
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal, with Box-Muller
+    pub fn gaussian(&mut self) -> f64 {
+        let u = 1.0 - self.unit();
+        let v = self.unit();
+        (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos()
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
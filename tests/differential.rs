@@ -0,0 +1,214 @@
+// Randomized differential tests: generate inputs with all sorts of station names, temperature
+// distributions and thread counts, and check the fast paths agree with the reference aggregator
+//
+// Set ONEBRC_SEED to replay a failing case and ONEBRC_CASES to run more of them
+
+mod common;
+
+use std::num::NonZeroUsize;
+
+use common::Rng;
+use onebrc_rs::{aggregate_bytes, Options, Scanner, Table};
+
+const DEFAULT_CASES: u64 = 200;
+const MAX_THREADS: usize = 8;
+
+#[derive(Debug, Clone, Copy)]
+enum Names {
+    /// A handful of short ASCII names, so every thread sees every station
+    Few,
+    /// Lots of distinct names, up to the challenge's maximum
+    Many,
+    /// Multi-byte UTF-8 names up to the 100 byte limit
+    Long,
+    /// Names full of characters that mean something elsewhere: dots, spaces, commas, braces, `=` and `/`
+    Awkward,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Temperatures {
+    Uniform,
+    /// Each station gets its own mean with a normal spread around it, like the official generator
+    Gaussian,
+    /// The same value for every measurement of a station
+    Constant,
+    /// Nothing but the most extreme values
+    Extremes,
+    /// Values close to zero, where signs and rounding are easy to get wrong
+    NearZero,
+}
+
+#[derive(Debug)]
+struct Case {
+    #[allow(dead_code)] // Only for the failure messages, to replay the case with ONEBRC_SEED
+    seed: u64,
+    names: Names,
+    temperatures: Temperatures,
+    decimals: u8,
+    threads: usize,
+    /// Bytes of input the threads take at a time
+    chunk_size: usize,
+    rows: usize,
+}
+
+fn name(rng: &mut Rng, kind: Names) -> String {
+    const ASCII: &[char] = &['a', 'b', 'k', 'q', 'z', 'A', 'M', 'Z', '0', '7', '-', '\''];
+    const UNICODE: &[char] = &['é', 'ü', 'ø', 'ß', 'Ω', 'ж', 'ש', 'ع', 'क', '東', '京', '🌡'];
+    const AWKWARD: &[char] = &['.', ' ', ',', '{', '}', '=', '/', '"', '\\', '\t', 'a'];
+
+    let (alphabet, max_len): (&[char], _) = match kind {
+        Names::Few => (ASCII, 6),
+        Names::Many => (ASCII, 16),
+        Names::Long => (UNICODE, 100),
+        Names::Awkward => (AWKWARD, 12),
+    };
+
+    let len = 1 + rng.below(max_len);
+    let mut name = String::new();
+    while name.chars().count() < len {
+        let c = *rng.pick(alphabet);
+        if name.len() + c.len_utf8() > 100 {
+            break;
+        }
+        name.push(c);
+    }
+    if name.is_empty() {
+        name.push('a');
+    }
+    name
+}
+
+fn generate(rng: &mut Rng, case: &Case) -> String {
+    let num_stations = match case.names {
+        Names::Few => 1 + rng.below(5),
+        Names::Many => 1 + rng.below(10_000),
+        Names::Long | Names::Awkward => 1 + rng.below(200),
+    };
+    let names = (0..num_stations)
+        .map(|_| name(rng, case.names))
+        .collect::<Vec<_>>();
+
+    let scale = 10i64.pow(case.decimals as u32);
+    let limit = 100 * scale - 1;
+    let means = (0..num_stations)
+        .map(|_| rng.range(-limit, limit))
+        .collect::<Vec<_>>();
+
+    let mut input = String::new();
+    for _ in 0..case.rows {
+        let station = rng.below(num_stations);
+        let value = match case.temperatures {
+            Temperatures::Uniform => rng.range(-limit, limit),
+            Temperatures::Gaussian => {
+                let value = means[station] + (rng.gaussian() * 10.0 * scale as f64) as i64;
+                value.clamp(-limit, limit)
+            }
+            Temperatures::Constant => means[station],
+            Temperatures::Extremes => *rng.pick(&[-limit, limit]),
+            Temperatures::NearZero => rng.range(-2, 2),
+        };
+        input.push_str(&names[station]);
+        input.push(';');
+        input.push_str(&common::format_value(value, case.decimals));
+        input.push('\n');
+    }
+    input
+}
+
+fn random_case(seed: u64) -> (Case, String) {
+    let mut rng = Rng::new(seed);
+    let names = *rng.pick(&[Names::Few, Names::Many, Names::Long, Names::Awkward]);
+    let temperatures = *rng.pick(&[
+        Temperatures::Uniform,
+        Temperatures::Gaussian,
+        Temperatures::Constant,
+        Temperatures::Extremes,
+        Temperatures::NearZero,
+    ]);
+    let decimals = if rng.chance(0.8) { 1 } else { 2 };
+    let threads = 1 + rng.below(MAX_THREADS);
+    // Mostly small inputs, where chunk boundaries land in interesting places, with the odd bigger one
+    let rows = if rng.chance(0.1) {
+        rng.range(10_000, 50_000)
+    } else {
+        rng.range(1, 2_000)
+    } as usize;
+
+    let mut case = Case {
+        seed,
+        names,
+        temperatures,
+        decimals,
+        threads,
+        chunk_size: 0,
+        rows,
+    };
+    let input = generate(&mut rng, &case);
+    // From a few lines to the whole input per chunk
+    case.chunk_size = 1 + rng.below(input.len().min(64 * 1024));
+
+    (case, input)
+}
+
+fn seeds() -> Vec<u64> {
+    if let Ok(seed) = std::env::var("ONEBRC_SEED") {
+        return vec![seed.parse().expect("ONEBRC_SEED should be a number")];
+    }
+    let cases = std::env::var("ONEBRC_CASES").map_or(DEFAULT_CASES, |n| {
+        n.parse().expect("ONEBRC_CASES should be a number")
+    });
+    (0..cases).collect()
+}
+
+#[test]
+fn matches_reference() {
+    for seed in seeds() {
+        let (case, input) = random_case(seed);
+        let expected = common::aggregate(&input, ';', case.decimals);
+        for table in Table::ALL {
+            let opts = Options {
+                threads: NonZeroUsize::new(case.threads),
+                chunk_size: case.chunk_size,
+                decimals: case.decimals,
+                checked: true,
+                table,
+                // Unsupported ones fall back to SWAR, which is covered anyway
+                scanner: Scanner::ALL[seed as usize % Scanner::ALL.len()],
+                ..Options::default()
+            };
+
+            let [mapped, streamed] = common::aggregate_both(input.as_bytes(), &opts);
+            for (stats, how) in [(mapped, "in memory"), (streamed, "streamed")] {
+                let what = format!("{how} with the {} table in {case:?}", table.name());
+                common::assert_matches(&stats.stations, &expected, &what);
+            }
+        }
+    }
+}
+
+#[test]
+fn thread_count_doesnt_matter() {
+    // Plenty of rows per thread, so every station ends up split across several partial maps
+    let mut rng = Rng::new(0x1B2C);
+    let case = Case {
+        seed: 0x1B2C,
+        names: Names::Few,
+        temperatures: Temperatures::Uniform,
+        decimals: 1,
+        threads: 0,
+        chunk_size: 0,
+        rows: 20_000,
+    };
+    let input = generate(&mut rng, &case);
+    let expected = common::aggregate(&input, ';', 1);
+
+    for threads in 1..=MAX_THREADS {
+        let opts = Options {
+            threads: NonZeroUsize::new(threads),
+            chunk_size: 4096,
+            ..Options::default()
+        };
+        let stats = aggregate_bytes(input.as_bytes(), &opts).unwrap();
+        common::assert_matches(&stats.stations, &expected, &format!("{threads} threads"));
+    }
+}
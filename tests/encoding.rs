@@ -0,0 +1,169 @@
+mod common;
+
+use std::num::NonZeroUsize;
+
+use onebrc_rs::{aggregate_bytes, aggregate_reader, Encoding, Error, ErrorPolicy, Options, Table};
+
+#[test]
+fn validates_like_std() {
+    // Mostly the bytes where validity changes, so every kind of bad sequence comes up
+    let bytes = [
+        b'a', b' ', 0x7F, 0x80, 0x8F, 0x90, 0x9F, 0xA0, 0xBF, 0xC0, 0xC1, 0xC2, 0xDF, 0xE0, 0xE1,
+        0xEC, 0xED, 0xEE, 0xEF, 0xF0, 0xF1, 0xF3, 0xF4, 0xF5, 0xFF,
+    ];
+    let mut rng = common::Rng::new(21);
+    let mut input = Vec::new();
+    let mut valid = Vec::new();
+    for i in 0..5000 {
+        // Long names go through the block at a time path, ASCII padding in front moves the interesting part around
+        let padding = if i % 2 == 0 { 0 } else { rng.below(40) };
+        let mut name = vec![b'x'; padding];
+        name.extend((0..1 + rng.below(6)).map(|_| *rng.pick(&bytes)));
+        if std::str::from_utf8(&name).is_ok() {
+            valid.push(String::from_utf8(name.clone()).unwrap());
+        }
+        input.extend_from_slice(&name);
+        input.extend_from_slice(b";1.0\n");
+    }
+    valid.sort();
+    valid.dedup();
+
+    for table in Table::ALL {
+        let opts = Options {
+            threads: NonZeroUsize::new(3),
+            chunk_size: 4096,
+            table,
+            on_error: ErrorPolicy::Skip,
+            ..Options::default()
+        };
+        for stats in common::aggregate_both(&input, &opts) {
+            let mut names = stats.stations.keys().cloned().collect::<Vec<_>>();
+            names.sort();
+            assert_eq!(names, valid);
+            let rows = stats.stations.values().map(|s| s.count).sum::<u64>();
+            assert_eq!(rows + stats.rejects.invalid_utf8, 5000);
+        }
+    }
+}
+
+#[test]
+fn fails_on_the_first_bad_name() {
+    let mut input = Vec::new();
+    for i in 0..2000 {
+        input.extend_from_slice(if i == 1234 || i == 1500 {
+            b"Z\xFCrich;1.0\n"
+        } else {
+            b"Zurich;1.0\n"
+        });
+    }
+    let opts = Options {
+        threads: NonZeroUsize::new(4),
+        chunk_size: 1024,
+        ..Options::default()
+    };
+    for result in [
+        aggregate_bytes(&input, &opts),
+        aggregate_reader(&input[..], &opts),
+    ] {
+        match result {
+            Err(Error::InvalidUtf8(e)) => {
+                assert_eq!(e.at.line, 1235);
+                assert_eq!(e.at.offset, 1234 * 11);
+                assert_eq!(e.snippet(), "Z\u{FFFD}rich;1.0");
+            }
+            other => panic!("expected invalid UTF-8, got {other:?}"),
+        }
+    }
+}
+
+#[test]
+fn latin1() {
+    let input = b"M\xFCnchen;1.0\nS\xE3o Paulo;20.0\nM\xFCnchen;3.0\nZ\xFCrich\xB05.0\n";
+    for separator in [';', '°'] {
+        let opts = Options {
+            threads: NonZeroUsize::new(2),
+            chunk_size: 8,
+            separator,
+            encoding: Encoding::Latin1,
+            ..Options::default()
+        };
+        let expected: &[&str] = match separator {
+            ';' => &["München", "São Paulo"],
+            _ => &["Zürich"],
+        };
+        for table in Table::ALL {
+            let opts = Options {
+                table,
+                on_error: ErrorPolicy::Skip,
+                ..opts.clone()
+            };
+            for stats in common::aggregate_both(input, &opts) {
+                let names = stats.sorted().iter().map(|(n, _)| *n).collect::<Vec<_>>();
+                assert_eq!(names, expected);
+            }
+        }
+    }
+}
+
+#[test]
+fn opaque_bytes() {
+    // Two different bad bytes, and backslashes in a valid and an invalid name
+    let input =
+        b"Z\xFCrich;1.0\nZ\xFErich;3.0\nZurich;5.0\n\xE9t\xE9;-1.0\nC:\\dir;7.0\nC:\\\xFC;9.0\n";
+    for table in Table::ALL {
+        let opts = Options {
+            threads: NonZeroUsize::new(2),
+            encoding: Encoding::Bytes,
+            table,
+            ..Options::default()
+        };
+        for stats in common::aggregate_both(input, &opts) {
+            let got = stats
+                .sorted()
+                .iter()
+                .map(|(name, s)| (name.to_string(), s.count, s.mean()))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                got,
+                [
+                    ("C:\\\\\\xFC".to_string(), 1, 90),
+                    ("C:\\dir".to_string(), 1, 70),
+                    ("Z\\xFCrich".to_string(), 1, 10),
+                    ("Z\\xFErich".to_string(), 1, 30),
+                    ("Zurich".to_string(), 1, 50),
+                    ("\\xE9t\\xE9".to_string(), 1, -10),
+                ]
+            );
+            assert_eq!(stats.rejects.total(), 0);
+        }
+    }
+}
+
+#[test]
+fn lossy_bytes() {
+    // Names that only differ in their bad bytes end up as the same station
+    let input = b"Z\xFCrich;1.0\nZ\xFErich;3.0\nZurich;5.0\nC:\\dir;7.0\n";
+    for table in Table::ALL {
+        let opts = Options {
+            threads: NonZeroUsize::new(2),
+            encoding: Encoding::Lossy,
+            table,
+            ..Options::default()
+        };
+        for stats in common::aggregate_both(input, &opts) {
+            let got = stats
+                .sorted()
+                .iter()
+                .map(|(name, s)| (name.to_string(), s.count, s.mean()))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                got,
+                [
+                    ("C:\\dir".to_string(), 1, 70),
+                    ("Zurich".to_string(), 1, 50),
+                    ("Z\u{FFFD}rich".to_string(), 2, 20),
+                ]
+            );
+        }
+    }
+}
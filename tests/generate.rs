@@ -0,0 +1,223 @@
+mod common;
+
+use std::{
+    collections::HashMap,
+    io::Read,
+    num::NonZeroUsize,
+    process::{Command, Stdio},
+};
+
+use onebrc_rs::{
+    aggregate_bytes,
+    generate::{generate, Config, NameLengths, Profile, MAX_NAME_LEN, OFFICIAL_STATIONS},
+    Options,
+};
+
+fn generated(config: &Config) -> String {
+    let mut out = Vec::new();
+    generate(&mut out, config).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn same_seed_same_output() {
+    let config = Config {
+        rows: 300_000,
+        seed: 42,
+        ..Config::default()
+    };
+
+    let one = generated(&Config {
+        threads: NonZeroUsize::new(1),
+        ..config.clone()
+    });
+    let many = generated(&Config {
+        threads: NonZeroUsize::new(5),
+        ..config.clone()
+    });
+    let other_seed = generated(&Config { seed: 43, ..config });
+
+    assert_eq!(one.lines().count(), 300_000);
+    assert_eq!(one, many);
+    assert_ne!(one, other_seed);
+}
+
+#[test]
+fn official_stations() {
+    let input = generated(&Config {
+        rows: 100_000,
+        ..Config::default()
+    });
+    let stats = aggregate_bytes(input.as_bytes(), &Options::default()).unwrap();
+
+    assert_eq!(stats.stations.len(), OFFICIAL_STATIONS);
+    // With a spread of 10 degrees the mean of ~240 readings lands within a couple of degrees of the official one
+    let mean = stats.stations["Kunming"].mean();
+    assert!((157 - 30..=157 + 30).contains(&mean), "{mean}");
+}
+
+#[test]
+fn station_count_and_name_lengths() {
+    for (name_lengths, lengths) in [
+        (NameLengths::Short, 1..=4),
+        (NameLengths::Long, 90..=MAX_NAME_LEN),
+        (NameLengths::Uniform, 1..=MAX_NAME_LEN),
+    ] {
+        let input = generated(&Config {
+            rows: 50_000,
+            stations: 1_000,
+            seed: 1,
+            name_lengths,
+            ..Config::default()
+        });
+        let stations = common::aggregate(&input, ';', 1);
+
+        assert_eq!(stations.len(), 1_000, "{name_lengths:?}");
+        for name in stations.keys() {
+            assert!(lengths.contains(&name.len()), "{name_lengths:?}: {name:?}");
+            assert_eq!(name.trim(), name);
+        }
+    }
+}
+
+#[test]
+fn adversarial_matches_reference() {
+    let input = generated(&Config {
+        rows: 200_000,
+        stations: 10_000,
+        seed: 7,
+        adversarial: true,
+        ..Config::default()
+    });
+    assert!(input.lines().any(|line| line.ends_with(";-0.0")));
+    assert!(input
+        .lines()
+        .any(|line| line.len() == MAX_NAME_LEN + ";99.9".len()));
+
+    let stats = aggregate_bytes(input.as_bytes(), &Options::default()).unwrap();
+    let expected = common::aggregate(&input, ';', 1);
+    common::assert_matches(&stats.stations, &expected, "adversarial");
+}
+
+#[test]
+fn adversarial_names_with_any_stations() {
+    for config in [Config::default(), Profile::Collisions.config()] {
+        let input = generated(&Config {
+            rows: 50_000,
+            seed: 1,
+            adversarial: true,
+            ..config
+        });
+        let stations = common::aggregate(&input, ';', 1);
+        let context = format!("{} stations", stations.len());
+
+        assert!(
+            stations.keys().any(|name| name.starts_with("Abcdefgh")),
+            "{context}"
+        );
+        assert!(
+            stations.keys().any(|name| name.len() == MAX_NAME_LEN),
+            "{context}"
+        );
+        assert!(stations.keys().any(|name| name.len() <= 2), "{context}");
+    }
+}
+
+#[test]
+fn closed_pipe() {
+    // Like `generate -o - | head`, far more rows than get read
+    let mut child = Command::new(env!("CARGO_BIN_EXE_onebrc-rs"))
+        .args([
+            "generate",
+            "--quiet",
+            "--seed",
+            "1",
+            "-o",
+            "-",
+            "10_000_000",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut head = [0; 4096];
+    child.stdout.take().unwrap().read_exact(&mut head).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.stderr.is_empty(), "{output:?}");
+}
+
+#[test]
+fn profiles() {
+    for profile in Profile::ALL {
+        assert_eq!(Profile::from_name(profile.name()), Some(profile));
+    }
+
+    let input = generated(&Config {
+        rows: 20_000,
+        seed: 3,
+        ..Profile::Collisions.config()
+    });
+    let stations = common::aggregate(&input, ';', 1);
+    // Same as the std table's FxHasher hashing a `[u8]` key: the length as a usize, then every byte
+    let hash = |name: &str| {
+        name.len()
+            .to_ne_bytes()
+            .into_iter()
+            .chain(name.bytes())
+            .fold(0u64, |h, b| h.wrapping_mul(0x0100_0000_01b3) ^ b as u64)
+    };
+    // Same as the open table's hash: mixing a word at a time like FxHasher, then MurmurHash3's finalizer
+    let open_hash = |name: &str| {
+        let mut h = name
+            .as_bytes()
+            .chunks(8)
+            .map(|word| {
+                let mut padded = [0; 8];
+                padded[..word.len()].copy_from_slice(word);
+                u64::from_le_bytes(padded)
+            })
+            .fold(0u64, |h, w| {
+                (h.rotate_left(5) ^ w).wrapping_mul(0x517c_c1b7_2722_0a95)
+            })
+            ^ name.len() as u64;
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        h ^= h >> 33;
+        h
+    };
+    // Half the names share their low bits for the std table, the other half their top bits, which pick the slot,
+    // for the open one
+    let most_common = |bits: &dyn Fn(&str) -> u64| {
+        let mut counts = HashMap::new();
+        for name in stations.keys() {
+            *counts.entry(bits(name)).or_insert(0) += 1;
+        }
+        counts.into_iter().max_by_key(|&(_, n)| n).unwrap().0
+    };
+    let low_bits = most_common(&|name| hash(name) & 0xFFFF);
+    let top_bits = most_common(&|name| open_hash(name) >> 54);
+    let (std, open) = stations
+        .keys()
+        .partition::<Vec<_>, _>(|name| hash(name) & 0xFFFF == low_bits);
+    assert!(open.iter().all(|name| open_hash(name) >> 54 == top_bits));
+    // Each of the 10,000 stations has about an 86% chance of showing up in 20,000 rows
+    assert!(
+        std.len() > 4_000 && open.len() > 4_000,
+        "{} {}",
+        std.len(),
+        open.len()
+    );
+
+    let input = generated(&Config {
+        rows: 20_000,
+        seed: 3,
+        ..Profile::Zipf.config()
+    });
+    let stations = common::aggregate(&input, ';', 1);
+    // With an exponent of 1 the most common of 413 stations gets about 15% of the rows
+    let top = stations.values().map(|s| s.count).max().unwrap();
+    assert!(top > 2_000, "{top}");
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use std::num::NonZeroUsize;
+
+use onebrc_rs::{
+    generate::{generate, Config, Profile},
+    Options, Table,
+};
+
+#[test]
+fn any_number_of_threads() {
+    // Every thread sees most of the 10,000 stations, and odd counts leave one partial out of some merge rounds
+    let mut input = Vec::new();
+    let config = Config {
+        rows: 50_000,
+        seed: 19,
+        threads: NonZeroUsize::new(2),
+        ..Profile::Unique10k.config()
+    };
+    generate(&mut input, &config).unwrap();
+    let input = String::from_utf8(input).unwrap();
+    let expected = common::aggregate(&input, ';', 1);
+
+    for table in Table::ALL {
+        for threads in [2, 3, 5, 16, 33] {
+            let opts = Options {
+                threads: NonZeroUsize::new(threads),
+                chunk_size: 16 * 1024,
+                table,
+                ..Options::default()
+            };
+            let what = format!("{threads} threads and the {} table", table.name());
+            for stats in common::aggregate_both(input.as_bytes(), &opts) {
+                common::assert_matches(&stats.stations, &expected, &what);
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::{fs::File, num::NonZeroUsize, path::PathBuf};
+
+use onebrc_rs::{aggregate_bytes, aggregate_file, mmap::Input, Options};
+
+/// A file called `name` in a fresh temporary directory, holding `contents`
+fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("onebrc-mmap-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("measurements.txt");
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn mapped_like_in_memory() {
+    let contents = (0..50_000)
+        .map(|i| format!("station {};{}.{}\n", i % 97, i % 45 - 20, i % 10))
+        .collect::<String>();
+    let path = temp_file("mapped", contents.as_bytes());
+
+    let input = Input::open(&mut File::open(&path).unwrap()).unwrap();
+    assert!(matches!(input, Input::Mapped(_)));
+    assert_eq!(&*input, contents.as_bytes());
+
+    let opts = Options {
+        threads: NonZeroUsize::new(3),
+        chunk_size: 10_000,
+        ..Options::default()
+    };
+    let mapped = aggregate_file(&path, &opts).unwrap();
+    assert_eq!(
+        mapped.stations,
+        aggregate_bytes(contents.as_bytes(), &opts)
+            .unwrap()
+            .stations
+    );
+    assert_eq!(mapped.stations.len(), 97);
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn empty_file() {
+    let path = temp_file("empty", b"");
+    let input = Input::open(&mut File::open(&path).unwrap()).unwrap();
+    assert!(matches!(input, Input::Buffered(ref buf) if buf.is_empty()));
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn zero_length_files_are_read() {
+    // Says it's empty, but reading it gives the status of this process
+    let path = "/proc/self/status";
+    assert_eq!(std::fs::metadata(path).unwrap().len(), 0);
+
+    let input = Input::open(&mut File::open(path).unwrap()).unwrap();
+    match &input {
+        Input::Buffered(buf) => assert!(buf.starts_with(b"Name:\t"), "{buf:?}"),
+        Input::Mapped(_) => panic!("mapped a zero-length file"),
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+#[test]
+fn falls_back_to_reading() {
+    // sysfs files say they're a page long and can't be mapped, the kernel fails the mapping with ENODEV
+    let path = "/sys/devices/system/cpu/online";
+    assert_eq!(std::fs::metadata(path).unwrap().len(), 4096);
+
+    let input = Input::open(&mut File::open(path).unwrap()).unwrap();
+    match &input {
+        Input::Buffered(buf) => {
+            assert_eq!(buf, &std::fs::read(path).unwrap());
+            assert!(buf.ends_with(b"\n") && buf.len() < 4096, "{buf:?}");
+        }
+        Input::Mapped(_) => panic!("mapped a sysfs file"),
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+#[test]
+fn mapping_errors_are_reported() {
+    // Mapping a gigabyte with a quarter of that in address space fails with ENOMEM, which reading wouldn't fix
+    let path = temp_file("enomem", b"");
+    File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_len(1 << 30)
+        .unwrap();
+
+    let output = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(r#"ulimit -v 262144 && exec "$0" --quiet --threads 1 "$1""#)
+        .arg(env!("CARGO_BIN_EXE_onebrc-rs"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("couldn't map file into memory") && stderr.contains("os error 12"),
+        "{stderr}"
+    );
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use std::{num::NonZeroUsize, process::Command};
+
+use onebrc_rs::{
+    names::{fold_case, nfc, nfd},
+    Collation, Normalization, Options,
+};
+
+fn names(input: &str, normalization: Normalization, collation: Collation) -> Vec<(String, u64)> {
+    let opts = Options {
+        threads: NonZeroUsize::new(2),
+        chunk_size: 64,
+        normalization,
+        ..Options::default()
+    };
+    let [a, b] = common::aggregate_both(input.as_bytes(), &opts).map(|stats| {
+        stats
+            .sorted_by(collation)
+            .into_iter()
+            .map(|(name, s)| (name.to_string(), s.count))
+            .collect::<Vec<_>>()
+    });
+    assert_eq!(a, b);
+    a
+}
+
+#[test]
+fn normal_forms() {
+    assert_eq!(nfc("Sa\u{303}o Paulo"), "S\u{E3}o Paulo");
+    assert_eq!(nfd("S\u{E3}o Paulo"), "Sa\u{303}o Paulo");
+    // Marks are put in canonical order before composing: dot below (220) goes before dot above (230)
+    assert_eq!(nfc("s\u{307}\u{323}"), "\u{1E69}");
+    assert_eq!(nfd("\u{1E69}"), "s\u{323}\u{307}");
+    // A mark of the same class in between blocks composition
+    assert_eq!(nfc("a\u{301}\u{301}"), "\u{E1}\u{301}");
+    // Composition exclusions stay decomposed
+    assert_eq!(nfc("\u{958}"), "\u{915}\u{93C}");
+    // Hangul syllables, with and without a final jamo
+    assert_eq!(nfd("\u{D55C}"), "\u{1112}\u{1161}\u{11AB}");
+    assert_eq!(
+        nfc("\u{1112}\u{1161}\u{11AB}\u{1100}\u{1173}"),
+        "\u{D55C}\u{ADF8}"
+    );
+    assert_eq!(nfc("Zürich"), "Zürich");
+}
+
+#[test]
+fn case_folding() {
+    assert_eq!(fold_case("ZÜRICH"), "zürich");
+    assert_eq!(fold_case("Straße"), "strasse");
+    assert_eq!(fold_case("ΣΊΣΥΦΟΣ"), "σίσυφοσ");
+    assert_eq!(fold_case("İstanbul"), "i\u{307}stanbul");
+}
+
+#[test]
+fn merges_spellings() {
+    let input = "São Paulo;1.0\nSa\u{303}o Paulo;2.0\n SÃO PAULO ;3.0\nGeneva;4.0\nGenève;5.0\n";
+    let off = names(input, Normalization::default(), Collation::Codepoint);
+    assert_eq!(off.len(), 5);
+
+    let nfc_only = Normalization {
+        nfc: true,
+        ..Normalization::default()
+    };
+    assert_eq!(
+        names(input, nfc_only, Collation::Codepoint),
+        [
+            (" SÃO PAULO ".to_string(), 1),
+            ("Geneva".to_string(), 1),
+            ("Genève".to_string(), 1),
+            ("São Paulo".to_string(), 2),
+        ]
+    );
+
+    let mut all = Normalization {
+        trim: true,
+        fold_case: true,
+        nfc: true,
+        ..Normalization::default()
+    };
+    // Aliases are looked up by the normalized name, in any spelling
+    all.add_alias("GENÈVE", "Geneva");
+    all.add_alias("Geneva", "Geneva");
+    all.add_alias("São Paulo", "São Paulo");
+    assert_eq!(
+        names(input, all, Collation::Codepoint),
+        [("Geneva".to_string(), 2), ("São Paulo".to_string(), 3)]
+    );
+}
+
+#[test]
+fn unicode_collation() {
+    let input = "Zwolle;1\nZürich;1\nzurich;1\nZurich;1\ncôte;1\nCote;1\ncote;1\nÅre;1\nAbu Dhabi;1\nStraße;1\nStrasbourg;1\n";
+    let sorted = |collation| {
+        names(input, Normalization::default(), collation)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        sorted(Collation::Codepoint),
+        [
+            "Abu Dhabi",
+            "Cote",
+            "Strasbourg",
+            "Straße",
+            "Zurich",
+            "Zwolle",
+            "Zürich",
+            "cote",
+            "côte",
+            "zurich",
+            "Åre"
+        ]
+    );
+    assert_eq!(
+        sorted(Collation::Unicode),
+        [
+            "Abu Dhabi",
+            "Åre",
+            "cote",
+            "Cote",
+            "côte",
+            "Strasbourg",
+            "Straße",
+            "zurich",
+            "Zurich",
+            "Zürich",
+            "Zwolle"
+        ]
+    );
+}
+
+#[test]
+fn command_line() {
+    let dir = std::env::temp_dir().join(format!("onebrc-names-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("measurements.txt");
+    std::fs::write(&input, "Genève;1.0\nGENEVA;3.0\nZürich;2.0\nZwolle;4.0\n").unwrap();
+    let aliases = dir.join("aliases.txt");
+    std::fs::write(&aliases, "# French to English\n\ngenève=Geneva\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_onebrc-rs"))
+        .arg(&input)
+        .args(["--quiet", "--normalize", "--collation", "unicode"])
+        .arg("--alias-file")
+        .arg(&aliases)
+        .args(["--alias", "geneva=Geneva"])
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "{Geneva=1.0/2.0/3.0, zürich=2.0/2.0/2.0, zwolle=4.0/4.0/4.0}\n"
+    );
+}
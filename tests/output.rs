@@ -0,0 +1,154 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+/// Run the binary on `input` through stdin
+fn run(input: &str, args: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_onebrc-rs"))
+        .args(["-", "--quiet"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // With bad arguments it exits without reading any of it
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn json() {
+    let input = "Zürich;12.5\nA \"quoted\", {odd} =name\\;-3.4\nZürich;-0.2\nbad\nTab\there;0.0\nBell\u{7};-0.5\n";
+    let output = run(input, &["--format", "json", "--on-error", "skip"]);
+    assert!(output.status.success(), "{output:?}");
+    let json = String::from_utf8(output.stdout).unwrap();
+
+    assert!(
+        json.starts_with(
+            "{\"metadata\":{\"input\":null,\"rows\":6,\"rejected\":1,\"stations\":4,\"decimals\":1,\"elapsed_ms\":{\"map\":"
+        ),
+        "{json}"
+    );
+    for phase in ["chunk", "parse", "merge", "sort", "total"] {
+        assert!(json.contains(&format!(",\"{phase}\":")), "{phase}");
+    }
+    assert!(json.ends_with(concat!(
+        "}},\"stations\":[",
+        "{\"name\":\"A \\\"quoted\\\", {odd} =name\\\\\",\"min\":-3.4,\"max\":-3.4,\"mean\":-3.4,\"sum\":-3.4,\"count\":1},",
+        "{\"name\":\"Bell\\u0007\",\"min\":-0.5,\"max\":-0.5,\"mean\":-0.5,\"sum\":-0.5,\"count\":1},",
+        "{\"name\":\"Tab\\there\",\"min\":0.0,\"max\":0.0,\"mean\":0.0,\"sum\":0.0,\"count\":1},",
+        "{\"name\":\"Zürich\",\"min\":-0.2,\"max\":12.5,\"mean\":6.2,\"sum\":12.3,\"count\":2}",
+        "]}\n"
+    )));
+}
+
+#[test]
+fn csv_and_tsv() {
+    let input = "Zürich;12.5\nSaint-Denis, \"Réunion\";-3.4\nZürich;-0.2\nTab\there;0.05\n";
+
+    let csv = run(input, &["--format", "csv"]);
+    assert!(csv.status.success(), "{csv:?}");
+    assert_eq!(
+        String::from_utf8(csv.stdout).unwrap(),
+        "name,min,mean,max\r\n\
+         \"Saint-Denis, \"\"Réunion\"\"\",-3.4,-3.4,-3.4\r\n\
+         Tab\there,0.1,0.1,0.1\r\n\
+         Zürich,-0.2,6.2,12.5\r\n"
+    );
+
+    // With a comma for the point, values need quoting in CSV but not in TSV, names with quotes need it in both
+    let args = [
+        "--columns",
+        "name,count,sum,max",
+        "--decimal-separator",
+        ",",
+    ];
+    let csv = run(input, &[&["--format", "csv"][..], &args].concat());
+    assert_eq!(
+        String::from_utf8(csv.stdout).unwrap(),
+        "name,count,sum,max\r\n\
+         \"Saint-Denis, \"\"Réunion\"\"\",1,\"-3,4\",\"-3,4\"\r\n\
+         Tab\there,1,\"0,1\",\"0,1\"\r\n\
+         Zürich,2,\"12,3\",\"12,5\"\r\n"
+    );
+    let tsv = run(input, &[&["--format", "tsv"][..], &args].concat());
+    assert_eq!(
+        String::from_utf8(tsv.stdout).unwrap(),
+        "name\tcount\tsum\tmax\n\
+         \"Saint-Denis, \"\"Réunion\"\"\"\t1\t-3,4\t-3,4\n\
+         \"Tab\there\"\t1\t0,1\t0,1\n\
+         Zürich\t2\t12,3\t12,5\n"
+    );
+
+    for bad in [
+        ["--columns", "name,median"],
+        ["--columns", ""],
+        ["--decimal-separator", "-"],
+        ["--decimal-separator", ",,"],
+        ["--decimal-separator", "\""],
+        ["--decimal-separator", "\r"],
+        ["--decimal-separator", "\n"],
+    ] {
+        assert!(!run(input, &bad).status.success(), "{bad:?}");
+    }
+}
+
+#[test]
+fn stddev_column() {
+    let input = "Oslo;2.0\nOslo;4.0\nOslo;4.0\nOslo;4.0\nOslo;5.0\nOslo;5.0\nOslo;7.0\nOslo;9.0\n";
+    let output = run(input, &["--format", "csv", "--columns", "name,stddev"]);
+    if cfg!(feature = "stddev") {
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "name,stddev\r\nOslo,2.0\r\n"
+        );
+    } else {
+        assert!(!output.status.success());
+    }
+}
+
+#[test]
+fn fixed_point_digits() {
+    let input = "Tiny;-0.0001\nTiny;0.0002\nBig;99.9999\nBig;-99.9999\nBig;99.9999\n";
+    let output = run(input, &["--decimals", "4", "--format", "json"]);
+    let json = String::from_utf8(output.stdout).unwrap();
+    assert!(json.contains(
+        "{\"name\":\"Big\",\"min\":-99.9999,\"max\":99.9999,\"mean\":33.3333,\"sum\":99.9999,\"count\":3}"
+    ));
+    assert!(json.contains(
+        "{\"name\":\"Tiny\",\"min\":-0.0001,\"max\":0.0002,\"mean\":0.0001,\"sum\":0.0001,\"count\":2}"
+    ));
+
+    let brace = run(input, &["--decimals", "4"]);
+    assert_eq!(
+        String::from_utf8(brace.stdout).unwrap(),
+        "{Big=-99.9999/33.3333/99.9999, Tiny=-0.0001/0.0001/0.0002}\n"
+    );
+}
+
+#[test]
+fn closed_pipe() {
+    // Far more output than a pipe holds, so writing it fails once the reader is gone
+    let input = (0..50_000)
+        .map(|i| format!("Station {i};{}.5\n", i % 100))
+        .collect::<String>();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_onebrc-rs"))
+        .args(["-", "--quiet", "--format", "lines"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    drop(child.stdout.take());
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.stderr.is_empty(), "{output:?}");
+}
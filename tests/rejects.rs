@@ -0,0 +1,90 @@
+mod common;
+
+use std::{num::NonZeroUsize, process::Command};
+
+use onebrc_rs::{aggregate_bytes, ErrorPolicy, Options};
+
+/// Run the binary on a file holding `contents` with `--on-error report` and `args`, and read the sidecar it wrote
+fn sidecar(name: &str, contents: &str, args: &[&str]) -> String {
+    let dir = std::env::temp_dir().join(format!("onebrc-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("measurements.txt");
+    std::fs::write(&input, contents).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_onebrc-rs"))
+        .arg(&input)
+        .args(["--quiet", "--threads", "1", "--on-error", "report"])
+        .args(args)
+        .output()
+        .unwrap();
+    let rejects = std::fs::read_to_string(dir.join("measurements.txt.rejects"));
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success(), "{output:?}");
+    rejects.unwrap()
+}
+
+#[test]
+fn sidecar_file() {
+    let long = format!("{};12.3.4", "x".repeat(150));
+    let contents =
+        format!("Oslo;1.0\nTab\there \\ \"quoted\"\nCarriage\rreturn;warm\n{long}\nOslo;3.0\n");
+
+    // Every reject stays on its own row with four fields, and the long line isn't cut short
+    assert_eq!(
+        sidecar("rejects", &contents, &[]),
+        format!(
+            "line\toffset\treason\tcontent\n\
+             2\t9\tmissing separator\tTab\\there \\\\ \"quoted\"\n\
+             3\t29\tinvalid temperature\tCarriage\\rreturn;warm\n\
+             4\t50\tinvalid temperature\t{long}\n"
+        )
+    );
+    assert_eq!(
+        sidecar("max-rejects", &contents, &["--max-rejects", "1"]),
+        "line\toffset\treason\tcontent\n\
+         2\t9\tmissing separator\tTab\\there \\\\ \"quoted\"\n"
+    );
+}
+
+#[test]
+fn keeps_the_first_rejects() {
+    let input = (0..5_000)
+        .map(|i| match i % 700 {
+            3 => format!("bad line {i}\n"),
+            _ => format!("station {};{}.5\n", i % 13, i % 40),
+        })
+        .collect::<String>();
+
+    for chunk_size in [100, 1 << 20] {
+        let opts = Options {
+            threads: NonZeroUsize::new(3),
+            chunk_size,
+            on_error: ErrorPolicy::Report,
+            max_rejects: 4,
+            ..Options::default()
+        };
+        for stats in common::aggregate_both(input.as_bytes(), &opts) {
+            let lines = stats
+                .rejects
+                .lines
+                .iter()
+                .map(|e| e.line_error().unwrap().at.line)
+                .collect::<Vec<_>>();
+            assert_eq!(stats.rejects.total(), 8, "{chunk_size} byte chunks");
+            assert_eq!(lines, [4, 704, 1_404, 2_104], "{chunk_size} byte chunks");
+        }
+    }
+}
+
+#[test]
+fn snippet_ends_on_a_char() {
+    let line = format!("{};-", "é".repeat(100));
+    let opts = Options {
+        on_error: ErrorPolicy::Report,
+        ..Options::default()
+    };
+    let stats = aggregate_bytes(line.as_bytes(), &opts).unwrap();
+    let e = stats.rejects.lines[0].line_error().unwrap();
+    assert_eq!(e.content, line);
+    assert_eq!(e.snippet(), "é".repeat(60));
+}
@@ -0,0 +1,73 @@
+// Golden files: every `*.txt` in tests/samples comes with a `.out` holding what the challenge's reference
+// implementation prints for it. Most of them are modelled on the challenge's own samples, which are under the
+// Apache License 2.0, see tests/samples/NOTICE. `python3 scripts/official_samples.py` fetches the official pairs
+// unmodified into tests/samples/official, and they're checked the same way from then on.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+fn samples() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/samples");
+    let official = dir.join("official");
+    let mut samples = [dir.as_path(), &official]
+        .into_iter()
+        .filter(|dir| dir.is_dir())
+        .flat_map(|dir| fs::read_dir(dir).unwrap())
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .collect::<Vec<_>>();
+    samples.sort();
+    assert!(!samples.is_empty(), "no samples found");
+    samples
+}
+
+fn run(args: &[&str], input: &Path) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_onebrc-rs"))
+        .args(args)
+        .arg("--quiet")
+        .arg(input)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{} failed: {}",
+        input.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn check(args: &[&str]) {
+    for input in samples() {
+        let expected = fs::read_to_string(input.with_extension("out")).unwrap();
+        assert_eq!(
+            run(args, &input),
+            expected,
+            "{} with {args:?}",
+            input.display()
+        );
+    }
+}
+
+#[test]
+fn mapped() {
+    check(&["--threads", "1"]);
+}
+
+#[test]
+fn streamed() {
+    check(&["--threads", "1", "--stream"]);
+}
+
+#[test]
+fn threaded() {
+    // Most samples are a few lines long, so most threads get nothing at all
+    for threads in ["2", "4", "8"] {
+        check(&["--threads", threads]);
+        check(&["--threads", threads, "--chunk-size", "16"]);
+        check(&["--threads", threads, "--stream"]);
+    }
+}
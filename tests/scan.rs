@@ -0,0 +1,114 @@
+mod common;
+
+use std::num::NonZeroUsize;
+
+use onebrc_rs::{aggregate_bytes, ErrorPolicy, Options, Scanner};
+
+fn supported() -> Vec<Scanner> {
+    Scanner::ALL
+        .into_iter()
+        .filter(|s| s.is_supported())
+        .collect()
+}
+
+#[test]
+fn resolves_to_a_supported_scanner() {
+    assert!(Scanner::Swar.is_supported());
+    for scanner in Scanner::ALL {
+        assert_eq!(Scanner::from_name(scanner.name()), Some(scanner));
+        let resolved = scanner.resolve();
+        assert_ne!(resolved, Scanner::Auto);
+        assert!(resolved.is_supported());
+    }
+}
+
+#[test]
+fn delimiters_at_every_offset() {
+    // Names and values of every length, so separators and newlines land everywhere in and across 64 byte blocks
+    let mut rng = common::Rng::new(16);
+    let mut input = String::new();
+    for len in 0..150 {
+        let name = (0..len)
+            .map(|_| *rng.pick(&['a', 'Z', '.', ' ', 'é', '東']))
+            .collect::<String>();
+        let value = common::format_value(rng.range(-999, 999), 1);
+        input.push_str(&format!("{name};{value}\n"));
+    }
+
+    let crlf = input.replace('\n', "\r\n");
+    let inputs = [
+        input.trim_end().to_string(),
+        crlf.trim_end().to_string(),
+        input,
+        crlf,
+    ];
+    for input in inputs {
+        let expected = common::aggregate(&input, ';', 1);
+        for scanner in supported() {
+            let opts = Options {
+                threads: NonZeroUsize::new(1),
+                scanner,
+                ..Options::default()
+            };
+            for stats in common::aggregate_both(input.as_bytes(), &opts) {
+                common::assert_matches(&stats.stations, &expected, &format!("{scanner:?}"));
+            }
+        }
+    }
+}
+
+#[test]
+fn other_separators() {
+    for separator in [',', '\t', '|', '°'] {
+        let input = (0..500)
+            .map(|i| format!("station {}{separator}{}.{}\n", i % 37, i % 90, i % 10))
+            .collect::<String>();
+        let expected = common::aggregate(&input, separator, 1);
+        for scanner in supported() {
+            let opts = Options {
+                threads: NonZeroUsize::new(1),
+                separator,
+                scanner,
+                ..Options::default()
+            };
+            let stats = aggregate_bytes(input.as_bytes(), &opts).unwrap();
+            common::assert_matches(&stats.stations, &expected, &format!("{scanner:?}"));
+        }
+    }
+}
+
+#[test]
+fn scanners_agree_on_bad_lines() {
+    let padding = "x".repeat(61);
+    let input =
+        format!("a;1.0\nno separator\n{padding};2.0\n;\n\nb;1;2\nc;3.0\r\n{padding}\nd;4.0");
+    let report = |scanner| {
+        let opts = Options {
+            threads: NonZeroUsize::new(1),
+            on_error: ErrorPolicy::Report,
+            scanner,
+            ..Options::default()
+        };
+        let stats = aggregate_bytes(input.as_bytes(), &opts).unwrap();
+        let mut stations = stats.stations.keys().cloned().collect::<Vec<_>>();
+        stations.sort();
+        let rejects = stats
+            .rejects
+            .lines
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        (stations, rejects)
+    };
+
+    let (stations, rejects) = report(Scanner::Swar);
+    assert_eq!(stations, ["a", "c", "d", padding.as_str()]);
+    assert_eq!(rejects.len(), 5, "{rejects:?}");
+    for scanner in supported() {
+        assert_eq!(
+            report(scanner),
+            (stations.clone(), rejects.clone()),
+            "{scanner:?}"
+        );
+    }
+}
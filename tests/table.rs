@@ -0,0 +1,159 @@
+mod common;
+
+use std::num::NonZeroUsize;
+
+use onebrc_rs::{
+    aggregate_bytes,
+    generate::{generate, Config, Profile},
+    ErrorPolicy, Options, Table,
+};
+
+#[test]
+fn spills_past_capacity() {
+    // More stations than the challenge allows, the open table has to put the rest somewhere
+    let input = (0..25_000)
+        .map(|i| format!("station {};{}.{}\n", i % 12_000, i % 50, i % 10))
+        .collect::<String>();
+    let expected = common::aggregate(&input, ';', 1);
+
+    for threads in [1, 3] {
+        let opts = Options {
+            threads: NonZeroUsize::new(threads),
+            table: Table::Open,
+            ..Options::default()
+        };
+        for stats in common::aggregate_both(input.as_bytes(), &opts) {
+            common::assert_matches(&stats.stations, &expected, &format!("{threads} threads"));
+        }
+    }
+}
+
+#[test]
+fn colliding_names() {
+    let mut input = Vec::new();
+    let config = Config {
+        rows: 20_000,
+        seed: 5,
+        threads: NonZeroUsize::new(2),
+        ..Profile::Collisions.config()
+    };
+    generate(&mut input, &config).unwrap();
+    let input = String::from_utf8(input).unwrap();
+    let expected = common::aggregate(&input, ';', 1);
+
+    for table in Table::ALL {
+        let opts = Options {
+            threads: NonZeroUsize::new(2),
+            table,
+            ..Options::default()
+        };
+        for stats in common::aggregate_both(input.as_bytes(), &opts) {
+            common::assert_matches(
+                &stats.stations,
+                &expected,
+                &format!("the {} table", table.name()),
+            );
+        }
+    }
+}
+
+#[test]
+fn names_around_word_boundaries() {
+    // Every name length from empty to a few words, inline and not, with the separator at every position in a word
+    let input = (0..40)
+        .flat_map(|len| {
+            let name = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMN"[..len].to_string();
+            [format!("{name};1.0\n"), format!("{name};-2.5\n")]
+        })
+        .collect::<String>();
+
+    for separator in [';', '→'] {
+        let input = input.replace(';', &separator.to_string());
+        let expected = common::aggregate(&input, separator, 1);
+        let opts = Options {
+            threads: NonZeroUsize::new(1),
+            separator,
+            table: Table::Open,
+            ..Options::default()
+        };
+        for stats in common::aggregate_both(input.as_bytes(), &opts) {
+            common::assert_matches(
+                &stats.stations,
+                &expected,
+                &format!("separator {separator:?}"),
+            );
+        }
+    }
+}
+
+#[test]
+fn names_hashed_while_scanning() {
+    // Names of every length hashed as the scan goes over them, starting and ending anywhere in a block and split
+    // between threads that each count some of their rows
+    let input = (0..130)
+        .flat_map(|len| {
+            let name = "Abcdefghij".repeat(13)[..len].to_string();
+            (0..4).map(move |i| format!("{name};{i}.5\n"))
+        })
+        .collect::<String>();
+    let expected = common::aggregate(&input, ';', 1);
+
+    for chunk_size in [1, 100, 4_000] {
+        let opts = Options {
+            threads: NonZeroUsize::new(4),
+            chunk_size,
+            table: Table::Open,
+            ..Options::default()
+        };
+        for stats in common::aggregate_both(input.as_bytes(), &opts) {
+            common::assert_matches(
+                &stats.stations,
+                &expected,
+                &format!("{chunk_size} byte chunks"),
+            );
+        }
+    }
+
+    // Without a separator the scan runs off the end of the input while hashing
+    let unterminated = format!("{input}{}", "x".repeat(70));
+    let opts = Options {
+        threads: NonZeroUsize::new(1),
+        on_error: ErrorPolicy::Report,
+        table: Table::Open,
+        ..Options::default()
+    };
+    let stats = aggregate_bytes(unterminated.as_bytes(), &opts).unwrap();
+    assert_eq!(stats.rejects.total(), 1);
+}
+
+#[test]
+fn tables_agree_on_bad_lines() {
+    let input = "a;1.0\r\nno separator\n\nb;x\r\nc;2.0\r\nb;-3.5\r\nd;4.0";
+    let rejected = |table| {
+        let opts = Options {
+            threads: NonZeroUsize::new(1),
+            on_error: ErrorPolicy::Report,
+            table,
+            ..Options::default()
+        };
+        let stats = aggregate_bytes(input.as_bytes(), &opts).unwrap();
+        let mut stations = stats
+            .stations
+            .iter()
+            .map(|(name, s)| (name.clone(), s.min, s.max, s.count))
+            .collect::<Vec<_>>();
+        stations.sort();
+        let rejects = stats
+            .rejects
+            .lines
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        (stations, rejects)
+    };
+
+    let (stations, rejects) = rejected(Table::Open);
+    assert_eq!(rejected(Table::Std), (stations.clone(), rejects.clone()));
+    assert_eq!(stations.len(), 4);
+    assert_eq!(rejects.len(), 3, "{rejects:?}");
+}
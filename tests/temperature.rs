@@ -0,0 +1,104 @@
+use onebrc_rs::temperature::parse_tenths;
+
+#[test]
+fn every_challenge_value() {
+    for tenths in -999..=999 {
+        let abs: i32 = i32::abs(tenths);
+        let sign = if tenths < 0 { "-" } else { "" };
+
+        let text = format!("{sign}{}.{}", abs / 10, abs % 10);
+        assert_eq!(parse_tenths(text.as_bytes()), Some(tenths), "{text}");
+
+        // Same value with a padded integer part, which only the slow path takes
+        let padded = format!("{sign}0{}.{}", abs / 10, abs % 10);
+        assert_eq!(parse_tenths(padded.as_bytes()), Some(tenths), "{padded}");
+    }
+}
+
+#[test]
+fn negative_zero() {
+    assert_eq!(parse_tenths(b"-0.0"), Some(0));
+    assert_eq!(parse_tenths(b"-0.1"), Some(-1));
+}
+
+#[test]
+fn values_without_an_exact_float() {
+    // None of these can be represented exactly as a float
+    assert_eq!(parse_tenths(b"9.7"), Some(97));
+    assert_eq!(parse_tenths(b"-9.7"), Some(-97));
+    assert_eq!(parse_tenths(b"0.3"), Some(3));
+    assert_eq!(parse_tenths(b"-0.1"), Some(-1));
+}
+
+#[test]
+fn other_numeric_shapes() {
+    assert_eq!(parse_tenths(b"  12.3 "), Some(123));
+    assert_eq!(parse_tenths(b"+12.3"), Some(123));
+    assert_eq!(parse_tenths(b"12"), Some(120));
+    assert_eq!(parse_tenths(b"-12"), Some(-120));
+    assert_eq!(parse_tenths(b"12."), Some(120));
+    assert_eq!(parse_tenths(b".5"), Some(5));
+    assert_eq!(parse_tenths(b"-.5"), Some(-5));
+    assert_eq!(parse_tenths(b"123.4"), Some(1234));
+    assert_eq!(parse_tenths(b"12.34"), Some(123));
+    assert_eq!(parse_tenths(b"12.35"), Some(124));
+    assert_eq!(parse_tenths(b"-12.35"), Some(-124));
+    assert_eq!(parse_tenths(b"1.2\r"), Some(12));
+}
+
+#[test]
+fn rejects_garbage() {
+    for text in [
+        "",
+        " ",
+        "-",
+        "+",
+        ".",
+        "-.",
+        "NaN",
+        "nan",
+        "inf",
+        "-inf",
+        "1e2",
+        "1.2.3",
+        "--1.0",
+        "1-.0",
+        "a.b",
+        "1.a",
+        "/.0",
+        "1:.0",
+        "12.:",
+        "1,5",
+        "99999999999.0",
+    ] {
+        assert_eq!(parse_tenths(text.as_bytes()), None, "{text:?}");
+    }
+}
+
+#[test]
+fn more_decimals() {
+    use onebrc_rs::temperature::parse_fixed;
+    use onebrc_rs::ExcessPrecision::{Reject, Round};
+
+    assert_eq!(parse_fixed(b"12.3", 2, Round), Some(1230));
+    assert_eq!(parse_fixed(b"-12.34", 2, Round), Some(-1234));
+    assert_eq!(parse_fixed(b"1013.25", 3, Round), Some(1013250));
+    assert_eq!(parse_fixed(b"0.0001", 4, Round), Some(1));
+    assert_eq!(parse_fixed(b"7", 4, Round), Some(70000));
+    assert_eq!(parse_fixed(b"-99.9999", 4, Reject), Some(-999999));
+}
+
+#[test]
+fn excess_precision() {
+    use onebrc_rs::temperature::parse_fixed;
+    use onebrc_rs::ExcessPrecision::{Reject, Round};
+
+    assert_eq!(parse_fixed(b"12.345", 2, Round), Some(1235));
+    assert_eq!(parse_fixed(b"-12.345", 2, Round), Some(-1235));
+    assert_eq!(parse_fixed(b"12.344", 2, Round), Some(1234));
+    assert_eq!(parse_fixed(b"12.345", 2, Reject), None);
+    assert_eq!(parse_fixed(b"12.3", 1, Reject), Some(123));
+    assert_eq!(parse_fixed(b"12.34", 1, Reject), None);
+    // Trailing zeros don't add any precision
+    assert_eq!(parse_fixed(b"12.300", 2, Reject), Some(1230));
+}